@@ -1,5 +1,11 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rig::message::{ImageMediaType, UserContent};
+use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 use std::path::PathBuf;
+use tracing::warn;
+
+const MAX_VISION_IMAGE_SIZE: u32 = 5 * 1024 * 1024; // 5MB
 
 #[derive(Debug, Clone)]
 pub struct PendingFile {
@@ -7,12 +13,13 @@ pub struct PendingFile {
     pub path: PathBuf,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AttachmentInfo {
     pub filename: String,
     pub container_path: String,
     pub size: u32,
     pub content_type: Option<String>,
+    pub host_path: PathBuf,
 }
 
 impl AttachmentInfo {
@@ -34,4 +41,36 @@ impl AttachmentInfo {
         }
         out
     }
+
+    fn image_media_type(&self) -> Option<ImageMediaType> {
+        let mime = self.content_type.as_deref()?.split(';').next()?.trim();
+        match mime {
+            "image/png" => Some(ImageMediaType::PNG),
+            "image/jpeg" | "image/jpg" => Some(ImageMediaType::JPEG),
+            "image/gif" => Some(ImageMediaType::GIF),
+            "image/webp" => Some(ImageMediaType::WEBP),
+            _ => None,
+        }
+    }
+
+    pub async fn load_images(attachments: &[AttachmentInfo]) -> Vec<UserContent> {
+        let mut images = Vec::new();
+        for att in attachments {
+            let Some(media_type) = att.image_media_type() else {
+                continue;
+            };
+            if att.size > MAX_VISION_IMAGE_SIZE {
+                continue;
+            }
+            match tokio::fs::read(&att.host_path).await {
+                Ok(bytes) => images.push(UserContent::image_base64(
+                    STANDARD.encode(bytes),
+                    Some(media_type),
+                    None,
+                )),
+                Err(e) => warn!("Failed to read image '{}': {}", att.filename, e),
+            }
+        }
+        images
+    }
 }
@@ -1,31 +1,40 @@
+use crate::approval::ApprovalGate;
 use crate::config::Config;
+use crate::mcp::McpManager;
 use crate::memory::MemoryManager;
+use crate::personas::PersonaStore;
 use anyhow::Result;
 pub use attachment::{AttachmentInfo, PendingFile};
 use rig::providers::{anthropic, gemini, openai};
 use rig_agent::RigAgent;
-pub use rig_agent::{Agent, AgentResponse, StreamEvent};
+pub use rig_agent::{Agent, AgentResponse, RequestContext, StreamEvent};
 use std::sync::Arc;
-pub use user_info::UserInfo;
+pub use user_info::{Preference, UserInfo, UserPreferences};
 
 mod attachment;
 mod preamble;
 mod rig_agent;
 mod user_info;
 
-pub async fn create_agent(config: Config, memory: Arc<MemoryManager>) -> Result<Arc<dyn Agent>> {
+pub async fn create_agent(
+    config: Config,
+    memory: Arc<MemoryManager>,
+    mcp: Arc<McpManager>,
+    approvals: Arc<ApprovalGate>,
+    personas: Arc<PersonaStore>,
+) -> Result<Arc<dyn Agent>> {
     match config.api.provider.as_str() {
         "openai" => {
             let client: openai::CompletionsClient = openai::CompletionsClient::builder()
                 .api_key(&config.api.key)
                 .base_url(config.api.url.as_deref().unwrap_or(""))
                 .build()?;
-            let agent = RigAgent::new(config, memory, client).await?;
+            let agent = RigAgent::new(config, memory, mcp, approvals, personas, client).await?;
             Ok(agent as Arc<dyn Agent>)
         }
         "gemini" => {
             let client = gemini::Client::new(&config.api.key)?;
-            let agent = RigAgent::new(config, memory, client).await?;
+            let agent = RigAgent::new(config, memory, mcp, approvals, personas, client).await?;
             Ok(agent as Arc<dyn Agent>)
         }
         _ => {
@@ -33,7 +42,7 @@ pub async fn create_agent(config: Config, memory: Arc<MemoryManager>) -> Result<
                 .api_key(&config.api.key)
                 .base_url(config.api.url.as_deref().unwrap_or(""))
                 .build()?;
-            let agent = RigAgent::new(config, memory, client).await?;
+            let agent = RigAgent::new(config, memory, mcp, approvals, personas, client).await?;
             Ok(agent as Arc<dyn Agent>)
         }
     }
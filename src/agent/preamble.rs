@@ -1,3 +1,5 @@
+use crate::config::PersonaConfig;
+use crate::permissions::ToolPermissions;
 use once_cell::sync::Lazy;
 use std::fmt::Write;
 
@@ -5,6 +7,7 @@ const PREAMBLE_BEHAVIOR: &str = "# Behavior\n\
                                  - Use Discord markdown: # Header, **bold**, *italic*, `code`, ```codeblock```, > quote.\n\
                                  - Do NOT use ---, or HTML — they don't render in Discord.\n\
                                  - Match the user's language.\n\
+                                 - Follow the [User Preferences] section when present; it overrides these defaults.\n\
                                  - Execute multi-step tasks sequentially without asking confirmation at each step.\n\n";
 
 const PREAMBLE_ATTACHMENTS: &str = "# Attachments\n\
@@ -16,95 +19,225 @@ const PREAMBLE_MEMORY: &str = "# Memory\n\
                                - **Important Facts**: Key facts appear under '# Important Facts' in the prompt.\n\
                                - **Recent Conversations**: The last 5 turns are included for continuity.\n\
                                - **Related Past Conversations**: Semantically similar past turns are auto-retrieved.\n\
+                               - **Discord Context**: A [Discord Context] section, when present, shows the message chain the user is replying to and recent channel messages.\n\
                                Use search_memory for deeper recall.\n\n";
 
 static TIMEZONE: Lazy<String> =
     Lazy::new(|| iana_time_zone::get_timezone().unwrap_or_else(|_| "UTC".to_string()));
 
+/// Optional integrations whose tools can be offered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Integrations {
+    pub scheduler: bool,
+    pub discord: bool,
+    /// The request comes from a known user.
+    pub user: bool,
+    pub web_search: bool,
+    pub web_news: bool,
+    pub web_fetch: bool,
+}
+
 pub fn build_preamble(
-    is_owner: bool,
-    has_scheduler: bool,
-    has_web_search: bool,
-    has_web_news: bool,
-    has_web_fetch: bool,
+    permissions: &ToolPermissions,
+    persona: Option<&PersonaConfig>,
+    available: Integrations,
+    extra_tools: &[(&str, &str)],
 ) -> String {
     let now = chrono::Local::now();
     let mut preamble = String::with_capacity(2600);
     let _ = write!(
         preamble,
-        "You are RustClaw, an AI assistant running as a Discord bot.\n\
+        "You are {}, an AI assistant running as a Discord bot.\n\
          Current time: {} ({})\n\n",
+        persona
+            .and_then(|p| p.display_name.as_deref())
+            .unwrap_or("RustClaw"),
         now.format("%Y-%m-%d %H:%M:%S"),
         *TIMEZONE
     );
 
     preamble.push_str(PREAMBLE_BEHAVIOR);
-    preamble.push_str("# Tools\n");
-    preamble.push_str(
-        "- **run_command**: Execute shell commands in a persistent Docker container at /workspace. \
-         Python and Node.js are pre-installed (`python3`, `node`, `npm`). Installed packages persist.\n",
-    );
-    preamble.push_str(
-        "- **send_file**: Send a file from /workspace as a Discord attachment (max 8MB). \
-         Create the file first with run_command.\n",
-    );
-    preamble.push_str(
-        "- **typst_render**: Render Typst markup to PNG. Use for tables, math, or formatted content \
-         that Discord markdown can't display.\n",
-    );
-    preamble.push_str(
-        "- **search_memory**: Semantic search over past conversations. Use when the user asks about \
-         previous discussions or you need context beyond what's already in the prompt.\n",
-    );
-    preamble.push_str("- **important_list**: List all saved important facts.\n");
-    if is_owner {
-        preamble.push_str(
-            "- **important_add**: Save a key fact to persistent memory for long-term recall.\n",
-        );
-        preamble.push_str("- **important_delete**: Delete an important fact by ID.\n");
+    if let Some(persona) = persona.filter(|p| !p.instructions.trim().is_empty()) {
+        preamble.push_str("# Persona\n");
+        preamble.push_str(persona.instructions.trim());
+        preamble.push_str("\n\n");
     }
-    if has_web_search {
-        preamble
-            .push_str("- **web_search**: Search the web for current events or fact-checking.\n");
-        if has_web_news {
-            preamble
-                .push_str("- **web_news**: Search for recent news articles (Serper provider).\n");
+    preamble.push_str("# Tools\n");
+    let network_note = if !permissions.role.is_admin() && permissions.limits.disable_network {
+        " Network access is disabled."
+    } else {
+        ""
+    };
+    let builtin: [(&str, bool, String); 24] = [
+        (
+            "run_command",
+            true,
+            format!(
+                "Execute shell commands in a persistent Docker container at /workspace. \
+                 Python and Node.js are pre-installed (`python3`, `node`, `npm`). Installed packages persist.{}",
+                network_note
+            ),
+        ),
+        (
+            "send_file",
+            true,
+            "Send a file from /workspace as a Discord attachment (max 8MB). \
+             Create the file first with run_command."
+                .into(),
+        ),
+        (
+            "typst_render",
+            true,
+            "Render Typst markup to PNG. Use for tables, math, or formatted content \
+             that Discord markdown can't display."
+                .into(),
+        ),
+        (
+            "search_memory",
+            true,
+            "Semantic search over past conversations. Use when the user asks about \
+             previous discussions or you need context beyond what's already in the prompt."
+                .into(),
+        ),
+        (
+            "important_list",
+            true,
+            "List all saved important facts.".into(),
+        ),
+        (
+            "important_add",
+            true,
+            "Save a key fact to persistent memory for long-term recall.".into(),
+        ),
+        (
+            "important_delete",
+            true,
+            "Delete an important fact by ID.".into(),
+        ),
+        (
+            "web_search",
+            available.web_search,
+            "Search the web for current events or fact-checking.".into(),
+        ),
+        (
+            "web_news",
+            available.web_search && available.web_news,
+            "Search for recent news articles (Serper provider).".into(),
+        ),
+        (
+            "web_fetch",
+            available.web_fetch,
+            "Fetch a URL and get its content in LLM-friendly format.".into(),
+        ),
+        (
+            "weather",
+            true,
+            "Get current weather and forecast for a location.".into(),
+        ),
+        (
+            "search_youtube",
+            true,
+            "Search YouTube videos and get their metadata.".into(),
+        ),
+        (
+            "get_transcript",
+            true,
+            "Get the transcript of a YouTube video to summarize or quote it.".into(),
+        ),
+        (
+            "schedule",
+            available.scheduler,
+            "Create a recurring cron task.".into(),
+        ),
+        (
+            "list_schedules",
+            available.scheduler,
+            "List all scheduled tasks.".into(),
+        ),
+        (
+            "unschedule",
+            available.scheduler,
+            "Remove a task by ID.".into(),
+        ),
+        (
+            "read_channel_history",
+            available.discord,
+            "Read earlier messages in a channel, with their message IDs.".into(),
+        ),
+        (
+            "add_reaction",
+            available.discord,
+            "React to a message with an emoji.".into(),
+        ),
+        (
+            "create_thread",
+            available.discord,
+            "Open a public thread, optionally on a message.".into(),
+        ),
+        ("create_poll", available.discord, "Start a poll.".into()),
+        ("pin_message", available.discord, "Pin a message.".into()),
+        (
+            "send_to_channel",
+            available.discord,
+            "Post a message in another channel.".into(),
+        ),
+        (
+            "set_preference",
+            available.user,
+            "Save the user's preferred language, answer length, timezone, units or \
+             custom instructions for future answers."
+                .into(),
+        ),
+        (
+            "reset_container",
+            true,
+            "Stop, remove, and recreate the Docker sandbox from scratch.".into(),
+        ),
+    ];
+    let mut restricted = Vec::new();
+    for (name, available, description) in &builtin {
+        if !available {
+            continue;
         }
-    }
-    if has_web_fetch {
-        preamble
-            .push_str("- **web_fetch**: Fetch a URL and get its content in LLM-friendly format.\n");
-    }
-    preamble.push_str("- **weather**: Get current weather and forecast for a location.\n");
-    if has_scheduler {
-        preamble.push_str(
-            "- **schedule**: Create a recurring cron task. **list_schedules**: List all tasks.\n",
-        );
-        if is_owner {
-            preamble.push_str("- **unschedule**: Remove a task by ID.\n");
+        if permissions.allows(name) {
+            let _ = writeln!(preamble, "- **{}**: {}", name, description);
+        } else {
+            restricted.push(*name);
         }
     }
-    if is_owner {
-        preamble.push_str(
-            "- **reset_container**: Stop, remove, and recreate the Docker sandbox from scratch.\n",
-        );
+    for (name, description) in extra_tools {
+        let summary = description.lines().next().unwrap_or_default();
+        let summary: String = summary.chars().take(200).collect();
+        let _ = writeln!(preamble, "- **{}**: {}", name, summary);
     }
     preamble.push('\n');
 
     preamble.push_str(PREAMBLE_ATTACHMENTS);
     preamble.push_str(PREAMBLE_MEMORY);
 
-    if is_owner {
-        preamble.push_str(
-            "# Permissions: Owner\n\
-             Full administrative access. Owner-only tools: important_add, important_delete, unschedule, reset_container.\n",
+    if permissions.role.is_admin() {
+        let _ = writeln!(
+            preamble,
+            "# Permissions: {}\n\
+             Full administrative access. Owner-only tools: important_add, important_delete, unschedule, reset_container.",
+            permissions.role.label()
         );
     } else {
+        let _ = write!(
+            preamble,
+            "# Permissions: {}\n\
+             - Available tools are the ones listed in '# Tools' above (depends on configured integrations).\n",
+            permissions.role.label()
+        );
+        if !restricted.is_empty() {
+            let _ = writeln!(
+                preamble,
+                "- Restricted tools are not available: {}.",
+                restricted.join(", ")
+            );
+        }
         preamble.push_str(
-            "# Permissions: Regular User\n\
-             - Available tools are the ones listed in '# Tools' above (depends on configured integrations).\n\
-             - Restricted tools are not available: important_add, important_delete, unschedule, reset_container.\n\
-             - Do not reveal system config, env vars, internal paths, or file contents outside /workspace.\n\
+            "- Do not reveal system config, env vars, internal paths, or file contents outside /workspace.\n\
              - Do not attempt privilege escalation. Politely decline restricted requests.\n",
         );
     }
@@ -1,24 +1,46 @@
-use super::{AttachmentInfo, PendingFile, UserInfo, preamble::build_preamble};
-use crate::config::Config;
+use super::{
+    AttachmentInfo, PendingFile, UserInfo,
+    preamble::{Integrations, build_preamble},
+};
+use crate::approval::{ApprovalGate, ApprovalRequester};
+use crate::config::{Config, CustomToolConfig};
+use crate::mcp::{McpManager, McpToolSpec};
 use crate::memory::MemoryManager;
+use crate::permissions::{Requester, Role, ToolPermissions};
+use crate::personas::PersonaStore;
 use crate::scheduler::Scheduler;
 use crate::tools;
 use anyhow::Result;
 use async_trait::async_trait;
 use futures_util::StreamExt;
 use rig::{
+    OneOrMany,
     agent::MultiTurnStreamItem,
     client::CompletionClient,
     completion::{CompletionModel, GetTokenUsage},
-    streaming::{StreamedAssistantContent, StreamingPrompt},
+    message::{Message, UserContent},
+    streaming::{StreamedAssistantContent, StreamedUserContent, StreamingPrompt},
+    tool::{Tool, ToolDyn},
 };
 use rusty_ytdl::search::YouTube;
+use serenity::all::Http;
 use std::sync::Arc;
 use tokio::sync::{RwLock, mpsc};
+use tracing::warn;
 
 #[derive(Debug, Clone)]
 pub enum StreamEvent {
     TextDelta(String),
+    ReasoningDelta(String),
+    ToolCallStart {
+        id: String,
+        name: String,
+        summary: String,
+    },
+    ToolCallEnd {
+        id: String,
+        success: bool,
+    },
     Done,
     Error(String),
 }
@@ -26,13 +48,21 @@ pub enum StreamEvent {
 struct StreamParams {
     model: String,
     preamble: String,
-    prompt: String,
+    prompt: Message,
     disable_reasoning: bool,
-    is_owner: bool,
+    thinking_budget: Option<u32>,
+    temperature: Option<f64>,
+    role: Role,
+    permissions: ToolPermissions,
+    requester: ApprovalRequester,
     discord_channel_id: Option<u64>,
+    /// The requesting user's timezone preference.
+    timezone: Option<String>,
+    max_turns: usize,
     config: Arc<Config>,
     memory: Arc<MemoryManager>,
     scheduler: Option<Arc<Scheduler>>,
+    discord_http: Option<Arc<Http>>,
     pending_files: Arc<RwLock<Vec<PendingFile>>>,
     tx: mpsc::Sender<StreamEvent>,
 }
@@ -40,6 +70,62 @@ struct StreamParams {
 pub struct AgentResponse {
     pub text: String,
     pub files: Vec<PendingFile>,
+    pub total_tokens: u64,
+    pub turn_id: String,
+    /// The model that answered.
+    pub model: String,
+}
+
+#[derive(Default)]
+pub struct RequestContext<'a> {
+    pub role: Role,
+    pub discord_channel_id: Option<u64>,
+    pub guild_id: Option<u64>,
+    pub user_info: Option<&'a UserInfo>,
+    pub attachments: &'a [AttachmentInfo],
+    pub discord_context: &'a str,
+    pub max_turns: Option<usize>,
+    /// Memory turn to drop before answering, e.g. when regenerating a reply.
+    pub replace_turn: Option<&'a str>,
+    /// A cut-off answer to continue instead of starting a new one.
+    pub previous_response: Option<&'a str>,
+    /// Overrides `[api].model` for this request.
+    pub model: Option<&'a str>,
+    /// `discord_context` holds the thread history, so the global recent
+    /// turns are left out of the memory context.
+    pub thread_history: bool,
+}
+
+const DEFAULT_MAX_TURNS: usize = 50;
+const TOOL_SUMMARY_MAX_CHARS: usize = 60;
+const TOOL_SUMMARY_KEYS: [&str; 6] = ["command", "url", "query", "location", "path", "prompt"];
+
+fn custom_tools<'a>(
+    config: &'a Config,
+    permissions: &'a ToolPermissions,
+) -> impl Iterator<Item = &'a CustomToolConfig> {
+    config.custom_tools.iter().filter(move |t| {
+        (t.http.is_some() || t.command.is_some()) && permissions.allows_tool(&t.name, t.owner_only)
+    })
+}
+
+fn summarize_tool_args(args: &serde_json::Value) -> String {
+    let Some(obj) = args.as_object() else {
+        return String::new();
+    };
+    let value = TOOL_SUMMARY_KEYS
+        .iter()
+        .find_map(|key| obj.get(*key).and_then(|v| v.as_str()))
+        .or_else(|| obj.values().find_map(|v| v.as_str()))
+        .unwrap_or_default();
+
+    let line = value.lines().next().unwrap_or_default().replace('`', "'");
+    if line.chars().count() > TOOL_SUMMARY_MAX_CHARS {
+        let truncated: String = line.chars().take(TOOL_SUMMARY_MAX_CHARS).collect();
+        format!("{}…", truncated)
+    } else {
+        line
+    }
 }
 
 #[async_trait]
@@ -49,17 +135,21 @@ pub trait Agent: Send + Sync {
     async fn process_streaming(
         &self,
         user_input: &str,
-        is_owner: bool,
-        discord_channel_id: Option<u64>,
-        user_info: Option<&UserInfo>,
-        attachments: &[AttachmentInfo],
+        request: RequestContext<'_>,
         tx: mpsc::Sender<StreamEvent>,
     ) -> Result<AgentResponse>;
+
+    /// Single prompt without tools or memory, e.g. for summarizing text.
+    /// Returns the answer and the tokens used.
+    async fn complete(&self, instruction: &str, input: &str) -> Result<(String, u64)>;
 }
 
 pub struct RigAgent<C: CompletionClient> {
     config: Config,
     memory: Arc<MemoryManager>,
+    mcp: Arc<McpManager>,
+    approvals: Arc<ApprovalGate>,
+    personas: Arc<PersonaStore>,
     scheduler: RwLock<Option<Arc<Scheduler>>>,
     http_client: reqwest::Client,
     yt: Arc<YouTube>,
@@ -67,10 +157,20 @@ pub struct RigAgent<C: CompletionClient> {
 }
 
 impl<C: CompletionClient> RigAgent<C> {
-    pub async fn new(config: Config, memory: Arc<MemoryManager>, client: C) -> Result<Arc<Self>> {
+    pub async fn new(
+        config: Config,
+        memory: Arc<MemoryManager>,
+        mcp: Arc<McpManager>,
+        approvals: Arc<ApprovalGate>,
+        personas: Arc<PersonaStore>,
+        client: C,
+    ) -> Result<Arc<Self>> {
         Ok(Arc::new(Self {
             config,
             memory,
+            mcp,
+            approvals,
+            personas,
             scheduler: RwLock::new(None),
             http_client: reqwest::Client::new(),
             yt: Arc::new(YouTube::new()?),
@@ -78,119 +178,270 @@ impl<C: CompletionClient> RigAgent<C> {
         }))
     }
 
-    async fn stream_prompt(&self, params: StreamParams) -> Result<String>
+    fn mcp_tools<'a>(
+        &'a self,
+        permissions: &'a ToolPermissions,
+    ) -> impl Iterator<Item = &'a Arc<McpToolSpec>> {
+        self.mcp
+            .tools()
+            .filter(move |t| permissions.allows_tool(&t.name, t.owner_only))
+    }
+
+    async fn stream_prompt(&self, params: StreamParams) -> Result<(String, u64)>
     where
         <C as CompletionClient>::CompletionModel: 'static,
     {
         let vector_db = params.memory.vector_db().clone();
 
-        let mut builder = self
-            .client
-            .agent(params.model)
-            .preamble(params.preamble.as_str())
-            .tool(tools::RunCommand {
-                config: params.config.clone(),
-            })
-            .tool(tools::ImportantList {
+        let permissions = &params.permissions;
+        let limits = &permissions.limits;
+
+        let mut toolset: Vec<Box<dyn ToolDyn>> = Vec::new();
+
+        // Also used by command-based custom tools, so they run with the same
+        // limits as run_command.
+        let sandbox = tools::RunCommand {
+            config: params.config.clone(),
+            offline: !permissions.role.is_admin() && limits.disable_network,
+            timeout: limits
+                .command_timeout
+                .filter(|_| !permissions.role.is_admin()),
+        };
+        if permissions.allows(tools::RunCommand::NAME) {
+            toolset.push(Box::new(sandbox.clone()));
+        }
+        if permissions.allows(tools::ImportantList::NAME) {
+            toolset.push(Box::new(tools::ImportantList {
                 vectordb: vector_db.clone(),
-            })
-            .tool(tools::SendFile {
+            }));
+        }
+        if permissions.allows(tools::SendFile::NAME) {
+            toolset.push(Box::new(tools::SendFile {
                 pending_files: params.pending_files.clone(),
                 config: params.config.clone(),
-            })
-            .tool(tools::TypstRender {
+            }));
+        }
+        if permissions.allows(tools::TypstRender::NAME) {
+            toolset.push(Box::new(tools::TypstRender {
                 pending_files: params.pending_files.clone(),
                 config: params.config.clone(),
-            })
-            .tool(tools::SearchMemory {
+            }));
+        }
+        if permissions.allows(tools::SearchMemory::NAME) {
+            toolset.push(Box::new(tools::SearchMemory {
                 vectordb: vector_db.clone(),
-            })
-            .tool(tools::Weather {
+                max_top_k: limits
+                    .search_memory_max_top_k
+                    .filter(|_| !permissions.role.is_admin())
+                    .unwrap_or(tools::SearchMemory::MAX_TOP_K)
+                    .min(tools::SearchMemory::MAX_TOP_K),
+            }));
+        }
+        if permissions.allows(tools::Weather::NAME) {
+            toolset.push(Box::new(tools::Weather {
                 client: self.http_client.clone(),
-            })
-            .tool(tools::SearchYouTube {
+            }));
+        }
+        if permissions.allows(tools::SearchYouTube::NAME) {
+            toolset.push(Box::new(tools::SearchYouTube {
                 yt: self.yt.clone(),
-            })
-            .tool(tools::GetTranscript {
+            }));
+        }
+        if permissions.allows(tools::GetTranscript::NAME) {
+            toolset.push(Box::new(tools::GetTranscript {
                 client: self.http_client.clone(),
-            });
-
+            }));
+        }
+        if permissions.allows(tools::ImportantAdd::NAME) {
+            toolset.push(Box::new(tools::ImportantAdd {
+                vectordb: vector_db.clone(),
+                is_owner: true,
+            }));
+        }
+        if permissions.allows(tools::ImportantDelete::NAME) {
+            toolset.push(Box::new(tools::ImportantDelete {
+                vectordb: vector_db.clone(),
+                is_owner: true,
+            }));
+        }
+        if let Some(user_id) = params.requester.user_id
+            && permissions.allows(tools::SetPreference::NAME)
         {
-            let mut extra = serde_json::Map::new();
-            if params.disable_reasoning {
-                extra.insert("thinking".into(), serde_json::json!({"type": "disabled"}));
-            }
-            extra.insert("parallel_tool_calls".into(), serde_json::json!(false));
-            builder = builder.additional_params(serde_json::Value::Object(extra));
+            toolset.push(Box::new(tools::SetPreference {
+                vectordb: vector_db.clone(),
+                user_id,
+            }));
         }
-
-        if params.is_owner {
-            builder = builder
-                .tool(tools::ImportantAdd {
-                    vectordb: vector_db.clone(),
-                    is_owner: true,
-                })
-                .tool(tools::ImportantDelete {
-                    vectordb: vector_db.clone(),
-                    is_owner: true,
-                })
-                .tool(tools::ResetContainer {
-                    config: params.config.clone(),
-                });
+        if permissions.allows(tools::ResetContainer::NAME) {
+            toolset.push(Box::new(tools::ResetContainer {
+                config: params.config.clone(),
+            }));
         }
 
-        if params.config.fetch.provider == "jina" {
-            builder = builder.tool(tools::WebFetch {
+        if params.config.fetch.provider == "jina" && permissions.allows(tools::WebFetch::NAME) {
+            toolset.push(Box::new(tools::WebFetch {
                 config: params.config.as_ref().clone(),
                 client: self.http_client.clone(),
-            });
+            }));
         }
 
         if params.config.search.api_key.is_some() {
-            builder = builder.tool(tools::WebSearch {
-                config: params.config.clone(),
-                client: self.http_client.clone(),
-            });
+            if permissions.allows(tools::WebSearch::NAME) {
+                toolset.push(Box::new(tools::WebSearch {
+                    config: params.config.clone(),
+                    client: self.http_client.clone(),
+                }));
+            }
 
-            if params.config.search.provider.as_deref().unwrap_or("") == "serper" {
-                builder = builder.tool(tools::WebNews {
+            if params.config.search.provider.as_deref().unwrap_or("") == "serper"
+                && permissions.allows(tools::WebNews::NAME)
+            {
+                toolset.push(Box::new(tools::WebNews {
                     config: params.config.clone(),
                     client: self.http_client.clone(),
-                });
+                }));
             }
         }
 
         if let Some(ref scheduler) = params.scheduler {
-            builder = builder
-                .tool(tools::ScheduleAdd {
+            if permissions.allows(tools::ScheduleAdd::NAME) {
+                toolset.push(Box::new(tools::ScheduleAdd {
                     scheduler: scheduler.clone(),
-                    is_owner: params.is_owner,
+                    role: params.role,
                     discord_channel_id: params.discord_channel_id,
-                })
-                .tool(tools::ScheduleList {
+                    timezone: params.timezone.clone(),
+                }));
+            }
+            if permissions.allows(tools::ScheduleList::NAME) {
+                toolset.push(Box::new(tools::ScheduleList {
                     scheduler: scheduler.clone(),
-                });
-            if params.is_owner {
-                builder = builder.tool(tools::ScheduleDelete {
+                }));
+            }
+            if permissions.allows(tools::ScheduleDelete::NAME) {
+                toolset.push(Box::new(tools::ScheduleDelete {
                     scheduler: scheduler.clone(),
                     is_owner: true,
-                });
+                }));
+            }
+        }
+
+        if let Some(http) = params.discord_http {
+            let discord = tools::DiscordAccess {
+                http,
+                channel_id: params.discord_channel_id,
+                user_id: params.requester.user_id,
+            };
+            if permissions.allows(tools::ReadChannelHistory::NAME) {
+                toolset.push(Box::new(tools::ReadChannelHistory {
+                    discord: discord.clone(),
+                }));
+            }
+            if permissions.allows(tools::AddReaction::NAME) {
+                toolset.push(Box::new(tools::AddReaction {
+                    discord: discord.clone(),
+                }));
+            }
+            if permissions.allows(tools::CreateThreadTool::NAME) {
+                toolset.push(Box::new(tools::CreateThreadTool {
+                    discord: discord.clone(),
+                }));
+            }
+            if permissions.allows(tools::CreatePollTool::NAME) {
+                toolset.push(Box::new(tools::CreatePollTool {
+                    discord: discord.clone(),
+                }));
+            }
+            if permissions.allows(tools::PinMessage::NAME) {
+                toolset.push(Box::new(tools::PinMessage {
+                    discord: discord.clone(),
+                }));
+            }
+            if permissions.allows(tools::SendToChannel::NAME) {
+                toolset.push(Box::new(tools::SendToChannel { discord }));
+            }
+        }
+
+        for spec in self.mcp_tools(permissions) {
+            toolset.push(Box::new(tools::McpTool { spec: spec.clone() }));
+        }
+
+        for def in custom_tools(&params.config, permissions) {
+            toolset.push(Box::new(tools::CustomTool {
+                def: def.clone(),
+                sandbox: sandbox.clone(),
+                client: self.http_client.clone(),
+            }));
+        }
+
+        if !permissions.role.is_admin() {
+            toolset = toolset
+                .into_iter()
+                .map(|tool| -> Box<dyn ToolDyn> {
+                    if self.approvals.requires_approval(&tool.name()) {
+                        Box::new(tools::RequiresApproval {
+                            inner: tool,
+                            gate: self.approvals.clone(),
+                            requester: params.requester.clone(),
+                        })
+                    } else {
+                        tool
+                    }
+                })
+                .collect();
+        }
+
+        let outcomes = tools::ToolOutcomes::default();
+        let toolset: Vec<Box<dyn ToolDyn>> = toolset
+            .into_iter()
+            .map(|tool| -> Box<dyn ToolDyn> {
+                Box::new(tools::TracksOutcome {
+                    inner: tool,
+                    outcomes: outcomes.clone(),
+                })
+            })
+            .collect();
+
+        let mut builder = self
+            .client
+            .agent(params.model)
+            .preamble(params.preamble.as_str())
+            .tools(toolset);
+        if let Some(temperature) = params.temperature {
+            builder = builder.temperature(temperature);
+        }
+
+        {
+            let mut extra = serde_json::Map::new();
+            if params.disable_reasoning {
+                extra.insert("thinking".into(), serde_json::json!({"type": "disabled"}));
+            } else if let Some(budget) = params.thinking_budget
+                && params.config.api.provider == "anthropic"
+            {
+                // Anthropic's request format; other providers reject the field.
+                extra.insert(
+                    "thinking".into(),
+                    serde_json::json!({"type": "enabled", "budget_tokens": budget}),
+                );
             }
+            extra.insert("parallel_tool_calls".into(), serde_json::json!(false));
+            builder = builder.additional_params(serde_json::Value::Object(extra));
         }
 
         Self::run_stream(
-            builder.default_max_turns(50).build(),
-            params.prompt.as_str(),
+            builder.default_max_turns(params.max_turns).build(),
+            params.prompt,
             params.tx,
+            outcomes,
         )
         .await
     }
 
     async fn run_stream<M, R, A>(
         agent: A,
-        prompt: &str,
+        prompt: Message,
         tx: mpsc::Sender<StreamEvent>,
-    ) -> Result<String>
+        outcomes: tools::ToolOutcomes,
+    ) -> Result<(String, u64)>
     where
         M: CompletionModel + 'static,
         R: Clone + Unpin + GetTokenUsage,
@@ -199,6 +450,7 @@ impl<C: CompletionClient> RigAgent<C> {
     {
         let mut stream = agent.stream_prompt(prompt).await;
         let mut response_text = String::new();
+        let mut total_tokens = 0;
 
         while let Some(item) = stream.next().await {
             match item {
@@ -209,6 +461,39 @@ impl<C: CompletionClient> RigAgent<C> {
                     response_text.push_str(&delta);
                     let _ = tx.send(StreamEvent::TextDelta(delta)).await;
                 }
+                Ok(MultiTurnStreamItem::StreamAssistantItem(
+                    StreamedAssistantContent::ReasoningDelta { reasoning, .. },
+                )) => {
+                    let _ = tx.send(StreamEvent::ReasoningDelta(reasoning)).await;
+                }
+                Ok(MultiTurnStreamItem::StreamAssistantItem(
+                    StreamedAssistantContent::ToolCall { tool_call, .. },
+                )) => {
+                    outcomes.start(
+                        &tool_call.id,
+                        &tool_call.function.name,
+                        &tool_call.function.arguments,
+                    );
+                    let _ = tx
+                        .send(StreamEvent::ToolCallStart {
+                            id: tool_call.id.clone(),
+                            summary: summarize_tool_args(&tool_call.function.arguments),
+                            name: tool_call.function.name,
+                        })
+                        .await;
+                }
+                Ok(MultiTurnStreamItem::StreamUserItem(StreamedUserContent::ToolResult {
+                    tool_result,
+                    ..
+                })) => {
+                    let success = outcomes.take(&tool_result.id).unwrap_or(false);
+                    let _ = tx
+                        .send(StreamEvent::ToolCallEnd {
+                            id: tool_result.id,
+                            success,
+                        })
+                        .await;
+                }
                 Ok(MultiTurnStreamItem::FinalResponse(res)) => {
                     let final_text = res.response().to_string();
                     if response_text.is_empty() {
@@ -219,6 +504,7 @@ impl<C: CompletionClient> RigAgent<C> {
                         let _ = tx.send(StreamEvent::TextDelta(remaining.to_string())).await;
                     }
                     response_text = final_text;
+                    total_tokens = res.usage().total_tokens;
                 }
                 Err(e) => {
                     let _ = tx.send(StreamEvent::Error(e.to_string())).await;
@@ -229,7 +515,7 @@ impl<C: CompletionClient> RigAgent<C> {
         }
 
         let _ = tx.send(StreamEvent::Done).await;
-        Ok(response_text)
+        Ok((response_text, total_tokens))
     }
 }
 
@@ -243,22 +529,54 @@ where
         *self.scheduler.write().await = Some(scheduler);
     }
 
+    async fn complete(&self, instruction: &str, input: &str) -> Result<(String, u64)> {
+        let agent = self
+            .client
+            .agent(self.config.api.model.clone())
+            .preamble(instruction)
+            .build();
+        let (tx, mut rx) = mpsc::channel(128);
+        let drain = tokio::spawn(async move { while rx.recv().await.is_some() {} });
+        let result = Self::run_stream(agent, Message::user(input), tx, Default::default()).await;
+        let _ = drain.await;
+        result
+    }
+
     async fn process_streaming(
         &self,
         user_input: &str,
-        is_owner: bool,
-        discord_channel_id: Option<u64>,
-        user_info: Option<&UserInfo>,
-        attachments: &[AttachmentInfo],
+        request: RequestContext<'_>,
         tx: mpsc::Sender<StreamEvent>,
     ) -> Result<AgentResponse> {
-        let context = self.memory.get_context(user_input).await?;
+        let RequestContext {
+            role,
+            discord_channel_id,
+            guild_id,
+            user_info,
+            attachments,
+            discord_context,
+            max_turns,
+            replace_turn,
+            previous_response,
+            model,
+            thread_history,
+        } = request;
+
+        let context = self
+            .memory
+            .get_context(user_input, !thread_history, replace_turn)
+            .await?;
         let user_section = user_info.map(|u| u.format_for_prompt()).unwrap_or_default();
         let attachment_section = AttachmentInfo::format_for_prompt(attachments);
 
         let mut full_prompt = String::new();
         full_prompt.reserve(
-            context.len() + user_section.len() + attachment_section.len() + user_input.len() + 16,
+            context.len()
+                + user_section.len()
+                + discord_context.len()
+                + attachment_section.len()
+                + user_input.len()
+                + 16,
         );
         if !context.is_empty() {
             full_prompt.push_str(&context);
@@ -268,47 +586,129 @@ where
             full_prompt.push_str(&user_section);
             full_prompt.push('\n');
         }
+        if !discord_context.is_empty() {
+            full_prompt.push_str(discord_context);
+            full_prompt.push('\n');
+        }
         if !attachment_section.is_empty() {
             full_prompt.push_str(&attachment_section);
             full_prompt.push('\n');
         }
         full_prompt.push_str("User: ");
         full_prompt.push_str(user_input);
+        if let Some(previous) = previous_response {
+            full_prompt.push_str("\n\n[Your previous answer was cut off]\n");
+            full_prompt.push_str(previous);
+            full_prompt.push_str(
+                "\n\nContinue exactly where the previous answer stopped. Do not repeat it.",
+            );
+        }
+
+        let mut content = OneOrMany::one(UserContent::text(full_prompt));
+        if self.config.model.vision {
+            for image in AttachmentInfo::load_images(attachments).await {
+                content.push(image);
+            }
+        }
 
         let scheduler_ref = self.scheduler.read().await.clone();
+        let discord_http = match &scheduler_ref {
+            Some(scheduler) => scheduler.discord_http().await,
+            None => None,
+        };
+        let persona = self
+            .personas
+            .resolve(discord_http.as_deref(), guild_id, discord_channel_id)
+            .await;
+        let mut permissions = ToolPermissions::resolve(
+            &self.config.permissions,
+            &Requester {
+                role,
+                user_id: user_info.map(|u| u.id),
+                roles: user_info.map(|u| u.roles.as_slice()).unwrap_or_default(),
+                guild_id,
+                channel_id: discord_channel_id,
+            },
+        );
+        if let Some(tools) = persona.and_then(|p| p.tools.as_deref()) {
+            permissions.restrict_to(tools);
+        }
         let preamble = build_preamble(
-            is_owner,
-            scheduler_ref.is_some(),
-            self.config.search.api_key.is_some(),
-            self.config.search.api_key.is_some()
-                && self.config.search.provider.as_deref().unwrap_or("") == "serper",
-            self.config.fetch.provider == "jina",
+            &permissions,
+            persona,
+            Integrations {
+                scheduler: scheduler_ref.is_some(),
+                discord: discord_http.is_some(),
+                user: user_info.is_some(),
+                web_search: self.config.search.api_key.is_some(),
+                web_news: self.config.search.provider.as_deref().unwrap_or("") == "serper",
+                web_fetch: self.config.fetch.provider == "jina",
+            },
+            &self
+                .mcp_tools(&permissions)
+                .map(|t| (t.name.as_str(), t.description.as_str()))
+                .chain(
+                    custom_tools(&self.config, &permissions)
+                        .map(|t| (t.name.as_str(), t.description.as_str())),
+                )
+                .collect::<Vec<_>>(),
         );
         let pending_files = Arc::new(RwLock::new(Vec::new()));
+        let model = model
+            .or(persona.and_then(|p| p.model.as_deref()))
+            .unwrap_or(&self.config.api.model)
+            .to_string();
 
-        let response = self
+        let (response, total_tokens) = self
             .stream_prompt(StreamParams {
-                model: self.config.api.model.clone(),
+                model: model.clone(),
                 preamble,
-                prompt: full_prompt,
+                prompt: Message::User { content },
                 disable_reasoning: self.config.model.disable_reasoning,
-                is_owner,
+                thinking_budget: self.config.model.thinking_budget,
+                temperature: persona.and_then(|p| p.temperature),
+                role,
+                permissions,
+                requester: ApprovalRequester {
+                    user_id: user_info.map(|u| u.id),
+                    user_name: user_info.map(|u| u.name.clone()).unwrap_or_default(),
+                    channel_id: discord_channel_id,
+                },
                 discord_channel_id,
+                timezone: user_info.and_then(|u| u.preferences.timezone.clone()),
+                max_turns: max_turns.unwrap_or(DEFAULT_MAX_TURNS),
                 config: Arc::new(self.config.clone()),
                 memory: self.memory.clone(),
                 scheduler: scheduler_ref,
+                discord_http,
                 pending_files: pending_files.clone(),
                 tx,
             })
             .await?;
 
         let author = user_info.map(|u| u.name.as_str()).unwrap_or("User");
-        self.memory.add_turn(author, user_input, &response).await?;
+        let remembered = match previous_response {
+            Some(previous) => format!("{}{}", previous, response),
+            None => response.clone(),
+        };
+        let turn_id = self
+            .memory
+            .add_turn(author, user_input, &remembered)
+            .await?;
+        // The replaced turn is only dropped once its replacement is saved.
+        if let Some(id) = replace_turn
+            && let Err(e) = self.memory.forget_turn(id).await
+        {
+            warn!("Failed to forget replaced turn {}: {}", id, e);
+        }
         let files = pending_files.read().await.clone();
 
         Ok(AgentResponse {
             text: response,
             files,
+            total_tokens,
+            turn_id,
+            model,
         })
     }
 }
@@ -1,5 +1,12 @@
+use anyhow::{Result, bail};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
 use std::fmt::Write;
-#[derive(Debug, Clone, Default)]
+
+const MAX_VALUE_CHARS: usize = 50;
+const MAX_INSTRUCTIONS_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UserInfo {
     pub name: String,
     pub global_name: Option<String>,
@@ -7,6 +14,150 @@ pub struct UserInfo {
     pub id: u64,
     pub roles: Vec<String>,
     pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub preferences: UserPreferences,
+}
+
+/// One setting of a [`UserPreferences`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preference {
+    Language,
+    Verbosity,
+    Timezone,
+    Units,
+    Instructions,
+}
+
+impl Preference {
+    pub const ALL: [Preference; 5] = [
+        Preference::Language,
+        Preference::Verbosity,
+        Preference::Timezone,
+        Preference::Units,
+        Preference::Instructions,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preference::Language => "language",
+            Preference::Verbosity => "verbosity",
+            Preference::Timezone => "timezone",
+            Preference::Units => "units",
+            Preference::Instructions => "instructions",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+/// Answer preferences a user stored for themselves.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserPreferences {
+    pub language: Option<String>,
+    /// `brief`, `normal` or `detailed`.
+    pub verbosity: Option<String>,
+    /// IANA name, e.g. `Europe/Berlin`.
+    pub timezone: Option<String>,
+    /// `metric` or `imperial`.
+    pub units: Option<String>,
+    pub instructions: Option<String>,
+}
+
+impl UserPreferences {
+    pub fn is_empty(&self) -> bool {
+        Preference::ALL.into_iter().all(|p| self.get(p).is_none())
+    }
+
+    pub fn get(&self, preference: Preference) -> Option<&str> {
+        match preference {
+            Preference::Language => self.language.as_deref(),
+            Preference::Verbosity => self.verbosity.as_deref(),
+            Preference::Timezone => self.timezone.as_deref(),
+            Preference::Units => self.units.as_deref(),
+            Preference::Instructions => self.instructions.as_deref(),
+        }
+    }
+
+    /// Validates and stores a preference. An empty value clears it.
+    pub fn set(&mut self, preference: Preference, value: &str) -> Result<()> {
+        let value = value.trim();
+        let lower = value.to_lowercase();
+        let value = match preference {
+            _ if value.is_empty() => None,
+            Preference::Verbosity => match lower.as_str() {
+                "brief" | "normal" | "detailed" => Some(lower),
+                _ => bail!("verbosity must be brief, normal or detailed"),
+            },
+            Preference::Units => match lower.as_str() {
+                "metric" | "imperial" => Some(lower),
+                _ => bail!("units must be metric or imperial"),
+            },
+            Preference::Timezone => match value.parse::<Tz>() {
+                Ok(tz) => Some(tz.name().to_string()),
+                Err(_) => bail!(
+                    "unknown timezone '{}', use an IANA name like Europe/Berlin",
+                    value
+                ),
+            },
+            Preference::Language if value.chars().count() > MAX_VALUE_CHARS => {
+                bail!("language is limited to {} characters", MAX_VALUE_CHARS)
+            }
+            Preference::Instructions if value.chars().count() > MAX_INSTRUCTIONS_CHARS => {
+                bail!(
+                    "instructions are limited to {} characters",
+                    MAX_INSTRUCTIONS_CHARS
+                )
+            }
+            Preference::Language | Preference::Instructions => Some(value.to_string()),
+        };
+
+        let slot = match preference {
+            Preference::Language => &mut self.language,
+            Preference::Verbosity => &mut self.verbosity,
+            Preference::Timezone => &mut self.timezone,
+            Preference::Units => &mut self.units,
+            Preference::Instructions => &mut self.instructions,
+        };
+        *slot = value;
+        Ok(())
+    }
+
+    /// The stored timezone, if it is still a valid one.
+    pub fn tz(&self) -> Option<Tz> {
+        self.timezone.as_deref()?.parse().ok()
+    }
+
+    fn format_for_prompt(&self, out: &mut String) {
+        if self.is_empty() {
+            return;
+        }
+        out.push_str("\n[User Preferences]");
+        if let Some(ref language) = self.language {
+            let _ = write!(out, "\nAnswer in: {}", language);
+        }
+        if let Some(ref verbosity) = self.verbosity {
+            let _ = write!(out, "\nAnswer length: {}", verbosity);
+        }
+        if let Some(tz) = self.tz() {
+            let now = chrono::Utc::now().with_timezone(&tz);
+            let _ = write!(
+                out,
+                "\nTimezone: {} (local time {})",
+                tz.name(),
+                now.format("%Y-%m-%d %H:%M")
+            );
+        }
+        if let Some(ref units) = self.units {
+            let _ = write!(out, "\nUnits: {}", units);
+        }
+        if let Some(ref instructions) = self.instructions {
+            out.push_str("\nInstructions: ");
+            out.push_str(instructions);
+        }
+    }
 }
 
 impl UserInfo {
@@ -48,6 +199,7 @@ impl UserInfo {
             out.push_str("\nAvatar: ");
             out.push_str(url);
         }
+        self.preferences.format_for_prompt(&mut out);
 
         out
     }
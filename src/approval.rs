@@ -0,0 +1,364 @@
+use crate::config::{ApprovalConfig, Config};
+use anyhow::Result;
+use serde::Serialize;
+use serenity::all::{
+    ButtonStyle, ChannelId, CreateActionRow, CreateButton, CreateMessage, EditMessage, Http,
+    MessageId, UserId,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, RwLock, oneshot};
+use tracing::{error, info};
+use uuid::Uuid;
+
+const CUSTOM_ID_PREFIX: &str = "approval";
+const MAX_ARGS_CHARS: usize = 1500;
+
+/// Who triggered a tool call that is waiting for approval.
+#[derive(Debug, Clone, Default)]
+pub struct ApprovalRequester {
+    pub user_id: Option<u64>,
+    pub user_name: String,
+    pub channel_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Approved { by: u64 },
+    Denied { by: u64 },
+    TimedOut,
+    Unavailable,
+}
+
+impl Decision {
+    fn label(&self) -> &'static str {
+        match self {
+            Decision::Approved { .. } => "approved",
+            Decision::Denied { .. } => "denied",
+            Decision::TimedOut => "timeout",
+            Decision::Unavailable => "unavailable",
+        }
+    }
+
+    fn decided_by(&self) -> Option<u64> {
+        match self {
+            Decision::Approved { by } | Decision::Denied { by } => Some(*by),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    id: &'a str,
+    tool: &'a str,
+    arguments: &'a str,
+    user_id: Option<u64>,
+    user_name: &'a str,
+    channel_id: Option<u64>,
+    decision: &'static str,
+    decided_by: Option<u64>,
+}
+
+/// Where an approval request was posted.
+type Posted = (Arc<Http>, ChannelId, MessageId);
+
+/// A request waiting for a decision. If the waiting tool call is dropped, e.g.
+/// because its reply was cancelled, it is settled as `Unavailable`.
+struct PendingRequest {
+    gate: Arc<ApprovalGate>,
+    id: String,
+    requester: ApprovalRequester,
+    tool: String,
+    arguments: String,
+    posted: Option<Posted>,
+    settled: bool,
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let gate = self.gate.clone();
+        let id = std::mem::take(&mut self.id);
+        let requester = std::mem::take(&mut self.requester);
+        let tool = std::mem::take(&mut self.tool);
+        let arguments = std::mem::take(&mut self.arguments);
+        let posted = self.posted.take();
+        runtime.spawn(async move {
+            gate.finish(
+                &id,
+                &requester,
+                &tool,
+                &arguments,
+                posted,
+                Decision::Unavailable,
+            )
+            .await;
+        });
+    }
+}
+
+/// Pauses sensitive tool calls until the owner approves or denies them with
+/// buttons on a Discord message. Every decision is appended to an audit log.
+pub struct ApprovalGate {
+    config: ApprovalConfig,
+    owner_id: UserId,
+    audit_path: PathBuf,
+    discord_http: RwLock<Option<Arc<Http>>>,
+    pending: Mutex<HashMap<String, oneshot::Sender<Decision>>>,
+}
+
+impl ApprovalGate {
+    pub fn new(config: &Config) -> Arc<Self> {
+        Arc::new(Self {
+            config: config.approval.clone(),
+            owner_id: UserId::new(config.discord.owner_id),
+            audit_path: config.storage.data_dir.join("approval_audit.jsonl"),
+            discord_http: RwLock::new(None),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn set_discord_http(&self, http: Arc<Http>) {
+        *self.discord_http.write().await = Some(http);
+    }
+
+    pub fn requires_approval(&self, tool: &str) -> bool {
+        self.config.tools.iter().any(|t| t == tool || t == "*")
+    }
+
+    /// Parses a button custom id into the request id and whether it approves.
+    pub fn parse_custom_id(custom_id: &str) -> Option<(&str, bool)> {
+        let mut parts = custom_id.splitn(3, ':');
+        if parts.next()? != CUSTOM_ID_PREFIX {
+            return None;
+        }
+        let approved = match parts.next()? {
+            "approve" => true,
+            "deny" => false,
+            _ => return None,
+        };
+        Some((parts.next()?, approved))
+    }
+
+    /// Delivers a button decision to the waiting tool call. Returns false when
+    /// the request is unknown or already decided.
+    pub async fn resolve(&self, id: &str, approved: bool, by: UserId) -> bool {
+        let Some(sender) = self.pending.lock().await.remove(id) else {
+            return false;
+        };
+        let by = by.get();
+        sender
+            .send(if approved {
+                Decision::Approved { by }
+            } else {
+                Decision::Denied { by }
+            })
+            .is_ok()
+    }
+
+    pub async fn request(
+        self: &Arc<Self>,
+        requester: &ApprovalRequester,
+        tool: &str,
+        arguments: &str,
+    ) -> Decision {
+        let id = Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id.clone(), tx);
+        let mut request = PendingRequest {
+            gate: self.clone(),
+            id,
+            requester: requester.clone(),
+            tool: tool.to_string(),
+            arguments: arguments.to_string(),
+            posted: None,
+            settled: false,
+        };
+
+        request.posted = match self
+            .post_request(&request.id, requester, tool, arguments)
+            .await
+        {
+            Ok(posted) => Some(posted),
+            Err(e) => {
+                error!("Failed to post approval request for '{}': {}", tool, e);
+                None
+            }
+        };
+
+        let decision = if request.posted.is_some() {
+            match tokio::time::timeout(Duration::from_secs(self.config.timeout), rx).await {
+                Ok(Ok(decision)) => decision,
+                Ok(Err(_)) => Decision::Unavailable,
+                Err(_) => Decision::TimedOut,
+            }
+        } else {
+            Decision::Unavailable
+        };
+        request.settled = true;
+        self.finish(
+            &request.id,
+            requester,
+            tool,
+            arguments,
+            request.posted.take(),
+            decision,
+        )
+        .await;
+
+        decision
+    }
+
+    /// Forgets a request, removes its buttons and records the decision.
+    async fn finish(
+        &self,
+        id: &str,
+        requester: &ApprovalRequester,
+        tool: &str,
+        arguments: &str,
+        posted: Option<Posted>,
+        decision: Decision,
+    ) {
+        self.pending.lock().await.remove(id);
+
+        if let Some((http, channel_id, message_id)) = posted {
+            let outcome = match decision {
+                Decision::Approved { by } => format!("✅ Approved by <@{}>", by),
+                Decision::Denied { by } => format!("⛔ Denied by <@{}>", by),
+                Decision::TimedOut => "⌛ Timed out".to_string(),
+                Decision::Unavailable => "⚠️ Cancelled".to_string(),
+            };
+            let content = format!(
+                "{}\n{}",
+                Self::format_request(requester, tool, arguments),
+                outcome
+            );
+            if let Err(e) = channel_id
+                .edit_message(
+                    &http,
+                    message_id,
+                    EditMessage::new().content(content).components(Vec::new()),
+                )
+                .await
+            {
+                error!("Failed to update approval message: {}", e);
+            }
+        }
+
+        info!(
+            "Approval for '{}' requested by {:?}: {}",
+            tool,
+            requester.user_id,
+            decision.label()
+        );
+        if let Err(e) = self.record(id, requester, tool, arguments, decision).await {
+            error!("Failed to write approval audit log: {}", e);
+        }
+    }
+
+    async fn post_request(
+        &self,
+        id: &str,
+        requester: &ApprovalRequester,
+        tool: &str,
+        arguments: &str,
+    ) -> Result<Posted> {
+        let http = self
+            .discord_http
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Discord is not connected"))?;
+
+        let channel_id = match self.config.channel_id {
+            Some(id) => ChannelId::new(id),
+            None => self.owner_id.create_dm_channel(&http).await?.id,
+        };
+
+        let buttons = vec![
+            CreateButton::new(format!("{}:approve:{}", CUSTOM_ID_PREFIX, id))
+                .label("Approve")
+                .style(ButtonStyle::Success),
+            CreateButton::new(format!("{}:deny:{}", CUSTOM_ID_PREFIX, id))
+                .label("Deny")
+                .style(ButtonStyle::Danger),
+        ];
+        let message = channel_id
+            .send_message(
+                &http,
+                CreateMessage::new()
+                    .content(Self::format_request(requester, tool, arguments))
+                    .components(vec![CreateActionRow::Buttons(buttons)]),
+            )
+            .await?;
+
+        Ok((http, channel_id, message.id))
+    }
+
+    fn format_request(requester: &ApprovalRequester, tool: &str, arguments: &str) -> String {
+        let mut args: String = arguments
+            .replace("```", "'''")
+            .chars()
+            .take(MAX_ARGS_CHARS)
+            .collect();
+        if arguments.chars().count() > MAX_ARGS_CHARS {
+            args.push('…');
+        }
+        let who = match requester.user_id {
+            Some(id) => format!("<@{}>", id),
+            None => requester.user_name.clone(),
+        };
+        let channel = requester
+            .channel_id
+            .map(|id| format!(" in <#{}>", id))
+            .unwrap_or_default();
+        format!(
+            "🔐 **Approval required**: `{}` requested by {}{}\n```json\n{}\n```",
+            tool, who, channel, args
+        )
+    }
+
+    async fn record(
+        &self,
+        id: &str,
+        requester: &ApprovalRequester,
+        tool: &str,
+        arguments: &str,
+        decision: Decision,
+    ) -> Result<()> {
+        let entry = AuditEntry {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            id,
+            tool,
+            arguments,
+            user_id: requester.user_id,
+            user_name: &requester.user_name,
+            channel_id: requester.channel_id,
+            decision: decision.label(),
+            decided_by: decision.decided_by(),
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        if let Some(parent) = self.audit_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.audit_path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use serenity::all::{ChannelId, Http};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+/// Parent channel of each thread seen so far, so that settings made for a
+/// channel also apply to the threads under it.
+#[derive(Default)]
+pub struct ThreadParents {
+    /// `None` for channels that aren't threads.
+    parents: RwLock<HashMap<u64, Option<u64>>>,
+}
+
+impl ThreadParents {
+    /// The parent channel of a thread, or `None` for other channels. Fetches
+    /// the channel the first time it is seen.
+    pub async fn parent_of(&self, http: &Http, channel_id: u64) -> Option<u64> {
+        if let Some(parent) = self.parents.read().await.get(&channel_id) {
+            return *parent;
+        }
+        let channel = ChannelId::new(channel_id).to_channel(http).await.ok()?;
+        let parent = channel
+            .guild()
+            .filter(|c| c.thread_metadata.is_some())
+            .and_then(|c| c.parent_id)
+            .map(|id| id.get());
+        self.parents.write().await.insert(channel_id, parent);
+        parent
+    }
+}
@@ -1,5 +1,6 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -17,12 +18,62 @@ pub struct Config {
     pub model: ModelConfig,
     #[serde(default)]
     pub embedding: EmbeddingConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub mcp: McpConfig,
+    #[serde(default)]
+    pub custom_tools: Vec<CustomToolConfig>,
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
+    #[serde(default)]
+    pub approval: ApprovalConfig,
+    #[serde(default)]
+    pub triggers: TriggersConfig,
+    #[serde(default)]
+    pub queue: QueueConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub threads: ThreadsConfig,
+    #[serde(default)]
+    pub context_menu: ContextMenuConfig,
+    #[serde(default)]
+    pub feedback: FeedbackConfig,
+    #[serde(default)]
+    pub personas: Vec<PersonaConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct DiscordConfig {
     pub token: String,
     pub owner_id: u64,
+    /// Users with the same rights as the owner.
+    #[serde(default)]
+    pub admin_users: Vec<u64>,
+    /// Role names whose members are admins in every guild.
+    #[serde(default)]
+    pub admin_roles: Vec<String>,
+    /// Users who can cancel streams and manage schedules.
+    #[serde(default)]
+    pub operator_users: Vec<u64>,
+    #[serde(default)]
+    pub operator_roles: Vec<String>,
+    /// Extra admin and operator role names, keyed by guild ID.
+    #[serde(default)]
+    pub guild_roles: HashMap<String, GuildRoles>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct GuildRoles {
+    #[serde(default)]
+    pub admin_roles: Vec<String>,
+    #[serde(default)]
+    pub operator_roles: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -74,6 +125,10 @@ fn default_timeout() -> u64 {
 pub struct ModelConfig {
     #[serde(default = "default_disable_reasoning")]
     pub disable_reasoning: bool,
+    pub thinking_budget: Option<u32>,
+    #[serde(default)]
+    pub vision: bool,
+    pub alternate: Option<String>,
 }
 
 fn default_disable_reasoning() -> bool {
@@ -93,16 +148,532 @@ fn default_embedding_provider() -> String {
     "local".to_string()
 }
 
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct LimitsConfig {
+    pub requests_per_minute: Option<u32>,
+    pub max_concurrent_per_user: Option<usize>,
+    pub max_concurrent_per_channel: Option<usize>,
+    pub daily_tokens_per_user: Option<u64>,
+    pub daily_tokens_per_guild: Option<u64>,
+    pub max_turns: Option<usize>,
+    #[serde(default)]
+    pub role_max_turns: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DisplayConfig {
+    #[serde(default)]
+    pub hide_tool_status: bool,
+    #[serde(default)]
+    pub collapse_tool_status: bool,
+    #[serde(default)]
+    pub reasoning: ReasoningMode,
+    #[serde(default)]
+    pub channel_reasoning: HashMap<String, ReasoningMode>,
+    #[serde(default)]
+    pub overflow: OverflowMode,
+    /// Messages a response may span before `overflow` takes over.
+    #[serde(default = "default_overflow_after")]
+    pub overflow_after: usize,
+}
+
+/// How responses longer than `overflow_after` messages are delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowMode {
+    /// Keep posting 2000-character messages.
+    #[default]
+    Messages,
+    /// One file attachment with a short summary.
+    Attachment,
+    /// Embeds with 4096-character descriptions.
+    Embed,
+    /// A file for code-heavy responses, embeds for prose.
+    Auto,
+}
+
+fn default_overflow_after() -> usize {
+    3
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            hide_tool_status: false,
+            collapse_tool_status: false,
+            reasoning: ReasoningMode::default(),
+            channel_reasoning: HashMap::new(),
+            overflow: OverflowMode::default(),
+            overflow_after: default_overflow_after(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningMode {
+    #[default]
+    Hide,
+    Spoiler,
+    Thread,
+}
+
+impl DisplayConfig {
+    pub fn reasoning_mode(&self, channel_id: u64) -> ReasoningMode {
+        self.channel_reasoning
+            .get(&channel_id.to_string())
+            .copied()
+            .unwrap_or(self.reasoning)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TriggersConfig {
+    #[serde(default = "default_true")]
+    pub mention: bool,
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub reply_to_bot: bool,
+    /// Answer every direct message, not just mentions.
+    #[serde(default)]
+    pub dms: bool,
+    #[serde(default)]
+    pub assistant_channels: Vec<u64>,
+    #[serde(default)]
+    pub allowed_guilds: Vec<u64>,
+    #[serde(default)]
+    pub allowed_channels: Vec<u64>,
+    #[serde(default)]
+    pub blocked_guilds: Vec<u64>,
+    #[serde(default)]
+    pub blocked_channels: Vec<u64>,
+    #[serde(default)]
+    pub overrides: HashMap<String, TriggerOverride>,
+    #[serde(default = "default_edit_window")]
+    pub edit_window: u64,
+}
+
+/// Per-guild or per-channel trigger settings. Unset fields inherit.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct TriggerOverride {
+    pub mention: Option<bool>,
+    pub prefix: Option<String>,
+    pub reply_to_bot: Option<bool>,
+}
+
+/// Effective trigger settings for one channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriggerModes {
+    pub mention: bool,
+    pub prefix: Option<String>,
+    pub reply_to_bot: bool,
+    pub all_messages: bool,
+}
+
+fn default_edit_window() -> u64 {
+    300
+}
+
+impl Default for TriggersConfig {
+    fn default() -> Self {
+        Self {
+            mention: true,
+            prefix: None,
+            reply_to_bot: false,
+            dms: false,
+            assistant_channels: Vec::new(),
+            allowed_guilds: Vec::new(),
+            allowed_channels: Vec::new(),
+            blocked_guilds: Vec::new(),
+            blocked_channels: Vec::new(),
+            overrides: HashMap::new(),
+            edit_window: default_edit_window(),
+        }
+    }
+}
+
+impl TriggersConfig {
+    /// Whether the bot may respond at all in this guild/channel. A thread
+    /// (`parent_id` set) is covered by its parent channel's entries. The lists
+    /// don't apply to DMs.
+    pub fn is_allowed(
+        &self,
+        guild_id: Option<u64>,
+        channel_id: u64,
+        parent_id: Option<u64>,
+    ) -> bool {
+        let Some(guild_id) = guild_id else {
+            return true;
+        };
+        let listed = |list: &[u64]| {
+            list.contains(&channel_id) || parent_id.is_some_and(|id| list.contains(&id))
+        };
+        !self.blocked_guilds.contains(&guild_id)
+            && !listed(&self.blocked_channels)
+            && (self.allowed_guilds.is_empty() || self.allowed_guilds.contains(&guild_id))
+            && (self.allowed_channels.is_empty() || listed(&self.allowed_channels))
+    }
+
+    /// Resolves the trigger modes for a channel: channel override, then the
+    /// parent channel's override for a thread, then guild override, then the
+    /// defaults.
+    pub fn modes(
+        &self,
+        guild_id: Option<u64>,
+        channel_id: u64,
+        parent_id: Option<u64>,
+    ) -> TriggerModes {
+        let channel = self.overrides.get(&channel_id.to_string());
+        let parent = parent_id.and_then(|id| self.overrides.get(&id.to_string()));
+        let guild = guild_id.and_then(|id| self.overrides.get(&id.to_string()));
+        let layers = [channel, parent, guild];
+        let pick = |f: fn(&TriggerOverride) -> Option<bool>, default: bool| {
+            layers.into_iter().flatten().find_map(f).unwrap_or(default)
+        };
+
+        TriggerModes {
+            mention: pick(|o| o.mention, self.mention),
+            prefix: layers
+                .into_iter()
+                .flatten()
+                .find_map(|o| o.prefix.clone())
+                .or_else(|| self.prefix.clone())
+                .filter(|p| !p.is_empty()),
+            reply_to_bot: pick(|o| o.reply_to_bot, self.reply_to_bot),
+            all_messages: (guild_id.is_none() && self.dms)
+                || self.assistant_channels.contains(&channel_id)
+                || parent_id.is_some_and(|id| self.assistant_channels.contains(&id)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct QueueConfig {
+    #[serde(default)]
+    pub scope: QueueScope,
+}
+
+/// Which requests wait for each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueScope {
+    #[default]
+    Channel,
+    User,
+    Guild,
+    Off,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ContextMenuConfig {
+    /// Answer message commands privately instead of replying to the message.
+    #[serde(default)]
+    pub ephemeral: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedbackConfig {
+    /// Add 👍/👎 buttons to finished answers and log them for `/feedback`.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Leave answers rated 👎 out of semantic recall. Off unless enabled.
+    #[serde(default)]
+    pub exclude_downvoted: bool,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exclude_downvoted: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThreadsConfig {
+    #[serde(default)]
+    pub mode: ThreadMode,
+    /// Answer length in characters that starts a thread in `long` mode.
+    #[serde(default = "default_thread_min_length")]
+    pub min_length: usize,
+    /// Thread messages fed to the model as context (max 100).
+    #[serde(default = "default_thread_history")]
+    pub history_messages: u8,
+    #[serde(default = "default_thread_max_chars")]
+    pub max_chars: usize,
+}
+
+/// When the bot starts a thread for a conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadMode {
+    #[default]
+    Off,
+    Always,
+    Long,
+}
+
+fn default_thread_min_length() -> usize {
+    1500
+}
+
+fn default_thread_history() -> u8 {
+    100
+}
+
+fn default_thread_max_chars() -> usize {
+    12000
+}
+
+impl Default for ThreadsConfig {
+    fn default() -> Self {
+        Self {
+            mode: ThreadMode::Off,
+            min_length: default_thread_min_length(),
+            history_messages: default_thread_history(),
+            max_chars: default_thread_max_chars(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShutdownConfig {
+    /// Seconds active replies get to finish before they are interrupted.
+    #[serde(default = "default_grace_period")]
+    pub grace_period: u64,
+}
+
+fn default_grace_period() -> u64 {
+    30
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: default_grace_period(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContextConfig {
+    #[serde(default = "default_reply_depth")]
+    pub reply_depth: usize,
+    #[serde(default)]
+    pub recent_messages: u8,
+    #[serde(default = "default_context_max_chars")]
+    pub max_chars: usize,
+}
+
+fn default_reply_depth() -> usize {
+    3
+}
+
+fn default_context_max_chars() -> usize {
+    4000
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            reply_depth: default_reply_depth(),
+            recent_messages: 0,
+            max_chars: default_context_max_chars(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct McpConfig {
+    #[serde(default)]
+    pub servers: Vec<McpServerConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpServerConfig {
+    pub name: String,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub owner_only: bool,
+    #[serde(default = "default_mcp_timeout")]
+    pub timeout: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_mcp_timeout() -> u64 {
+    60
+}
+
+/// A named persona layered on top of the default preamble.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PersonaConfig {
+    /// Key used by `/persona set`.
+    pub name: String,
+    /// Name the bot answers as; "RustClaw" when unset.
+    pub display_name: Option<String>,
+    /// Extra system prompt instructions.
+    #[serde(default)]
+    pub instructions: String,
+    /// Overrides `[api].model`.
+    pub model: Option<String>,
+    /// Tools the persona may use, within the user's permissions. All when unset.
+    pub tools: Option<Vec<String>>,
+    pub temperature: Option<f64>,
+    /// Guilds and channels that use this persona; a channel wins over its guild.
+    #[serde(default)]
+    pub guilds: Vec<u64>,
+    #[serde(default)]
+    pub channels: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomToolConfig {
+    pub name: String,
+    pub description: String,
+    #[serde(default = "default_tool_parameters")]
+    pub parameters: serde_json::Value,
+    pub http: Option<CustomHttpConfig>,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub owner_only: bool,
+    #[serde(default = "default_max_output")]
+    pub max_output: usize,
+    /// Seconds an `http` tool waits for the response.
+    #[serde(default = "default_custom_tool_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomHttpConfig {
+    #[serde(default = "default_http_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+fn default_tool_parameters() -> serde_json::Value {
+    serde_json::json!({"type": "object", "properties": {}})
+}
+
+fn default_max_output() -> usize {
+    8000
+}
+
+fn default_custom_tool_timeout() -> u64 {
+    30
+}
+
+fn default_http_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct PermissionsConfig {
+    pub default_tools: Option<Vec<String>>,
+    #[serde(default)]
+    pub rules: Vec<PermissionRule>,
+    #[serde(default)]
+    pub user_limits: ToolLimits,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct PermissionRule {
+    #[serde(default)]
+    pub users: Vec<u64>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub guilds: Vec<u64>,
+    #[serde(default)]
+    pub channels: Vec<u64>,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ToolLimits {
+    #[serde(default)]
+    pub disable_network: bool,
+    pub command_timeout: Option<u64>,
+    pub search_memory_max_top_k: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApprovalConfig {
+    #[serde(default)]
+    pub tools: Vec<String>,
+    pub channel_id: Option<u64>,
+    #[serde(default = "default_approval_timeout")]
+    pub timeout: u64,
+}
+
+fn default_approval_timeout() -> u64 {
+    300
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            tools: Vec::new(),
+            channel_id: None,
+            timeout: default_approval_timeout(),
+        }
+    }
+}
+
 impl Config {
     pub fn from_file(path: &str) -> Result<Self> {
         let content = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path))?;
 
         let config: Config = toml::from_str(&content).context("Failed to parse config file")?;
+        config.validate_custom_tools()?;
 
         Ok(config)
     }
 
+    /// Rejects custom tools that reuse a built-in tool's name or each other's.
+    fn validate_custom_tools(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for tool in &self.custom_tools {
+            if crate::tools::BUILTIN_TOOLS.contains(&tool.name.as_str()) {
+                bail!(
+                    "custom tool '{}' has the same name as a built-in tool",
+                    tool.name
+                );
+            }
+            if !names.insert(tool.name.as_str()) {
+                bail!("custom tool '{}' is configured more than once", tool.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Names MCP tools may not take: the built-in and custom tools.
+    pub fn reserved_tool_names(&self) -> Vec<&str> {
+        crate::tools::BUILTIN_TOOLS
+            .into_iter()
+            .chain(self.custom_tools.iter().map(|t| t.name.as_str()))
+            .collect()
+    }
+
     pub fn load() -> Result<Self> {
         Self::from_file("config.toml")
     }
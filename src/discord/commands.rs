@@ -0,0 +1,520 @@
+use super::*;
+use crate::{personas::DEFAULT_PERSONA, tools::RunCommand};
+use serenity::{
+    builder::{
+        CreateCommand, CreateCommandOption, CreateInteractionResponse,
+        CreateInteractionResponseMessage, EditInteractionResponse,
+    },
+    model::{
+        application::{CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue},
+        guild::Member,
+        id::GuildId,
+    },
+};
+use std::fmt::Write;
+use tracing::{error, info};
+
+const MEMORY_SEARCH_RESULTS: usize = 5;
+const PREVIEW_CHARS: usize = 120;
+
+pub(super) fn command_definitions() -> Vec<CreateCommand> {
+    let sub = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+    };
+    let text = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::String, name, description).required(true)
+    };
+
+    vec![
+        CreateCommand::new("cancelall")
+            .description("(Operators and admins) Cancel all active AI response streams"),
+        CreateCommand::new("memory")
+            .description("(Admin only) Inspect and prune conversation memory")
+            .add_option(
+                sub("search", "Semantic search over past conversations")
+                    .add_sub_option(text("query", "What to search for")),
+            )
+            .add_option(
+                sub("forget", "Delete a conversation turn")
+                    .add_sub_option(text("id", "Turn ID from /memory search")),
+            )
+            .add_option(sub("stats", "Show memory statistics")),
+        CreateCommand::new("important")
+            .description("(Admin only) Manage important facts")
+            .add_option(sub("list", "List all important facts"))
+            .add_option(
+                sub("add", "Save an important fact")
+                    .add_sub_option(text("content", "The fact to remember")),
+            )
+            .add_option(
+                sub("delete", "Delete an important fact")
+                    .add_sub_option(text("id", "Fact ID from /important list")),
+            ),
+        CreateCommand::new("schedule")
+            .description("(Operators and admins) Manage scheduled tasks")
+            .add_option(sub("list", "List all scheduled tasks"))
+            .add_option(
+                sub("add", "Create a recurring task in this channel")
+                    .add_sub_option(text("cron", "Cron expression, e.g. '0 9 * * *'"))
+                    .add_sub_option(text("prompt", "Prompt to run on schedule"))
+                    .add_sub_option(CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "description",
+                        "Short description of the task",
+                    )),
+            )
+            .add_option(
+                sub("delete", "Delete a scheduled task")
+                    .add_sub_option(text("id", "Task ID from /schedule list")),
+            )
+            .add_option(
+                sub("pause", "Pause or resume a scheduled task")
+                    .add_sub_option(text("id", "Task ID from /schedule list"))
+                    .add_sub_option(CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "resume",
+                        "Resume instead of pausing",
+                    )),
+            ),
+        CreateCommand::new("sandbox")
+            .description("(Admin only) Manage the Docker sandbox")
+            .add_option(sub("reset", "Remove the sandbox containers and workspace"))
+            .add_option(sub("status", "Show sandbox container state")),
+        CreateCommand::new("persona")
+            .description("(Admin only) Switch the persona used in this channel or server")
+            .add_option(sub("list", "List personas and where they are assigned"))
+            .add_option(
+                sub("set", "Use a persona here")
+                    .add_sub_option(text("name", "Persona name, or 'default'"))
+                    .add_sub_option(CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "server",
+                        "Apply to the whole server instead of this channel",
+                    )),
+            )
+            .add_option(
+                sub("clear", "Go back to the configured persona").add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "server",
+                        "Clear the server assignment instead of this channel's",
+                    ),
+                ),
+            ),
+        CreateCommand::new("status").description("(Operators and admins) Show bot status"),
+        CreateCommand::new("summarize")
+            .description("Summarize recent messages in this channel")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "messages",
+                    "How many recent messages to summarize (default 100)",
+                )
+                .min_int_value(1)
+                .max_int_value(2000),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "since_my_last",
+                "Summarize everything since your last message",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "hours",
+                    "Summarize the last N hours",
+                )
+                .min_int_value(1)
+                .max_int_value(720),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "schedule",
+                "(Admin only) Cron expression to post a summary of the last `hours` (default 24) regularly",
+            )),
+        feedback_command(),
+        prefs_command(),
+    ]
+    .into_iter()
+    .chain(menu_definitions())
+    .collect()
+}
+
+pub(super) fn subcommand<'a>(
+    cmd: &'a CommandInteraction,
+) -> Option<(&'a str, Vec<ResolvedOption<'a>>)> {
+    cmd.data
+        .options()
+        .into_iter()
+        .find_map(|opt| match opt.value {
+            ResolvedValue::SubCommand(options) => Some((opt.name, options)),
+            _ => None,
+        })
+}
+
+pub(super) fn string_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|opt| match opt.value {
+        ResolvedValue::String(value) if opt.name == name => Some(value),
+        _ => None,
+    })
+}
+
+pub(super) fn bool_option(options: &[ResolvedOption<'_>], name: &str) -> Option<bool> {
+    options.iter().find_map(|opt| match opt.value {
+        ResolvedValue::Boolean(value) if opt.name == name => Some(value),
+        _ => None,
+    })
+}
+
+pub(super) fn int_option(options: &[ResolvedOption<'_>], name: &str) -> Option<i64> {
+    options.iter().find_map(|opt| match opt.value {
+        ResolvedValue::Integer(value) if opt.name == name => Some(value),
+        _ => None,
+    })
+}
+
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > PREVIEW_CHARS {
+        let truncated: String = line.chars().take(PREVIEW_CHARS).collect();
+        format!("{}…", truncated)
+    } else {
+        line.to_string()
+    }
+}
+
+fn format_uptime(elapsed: std::time::Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!(
+        "{}d {}h {}m",
+        secs / 86_400,
+        (secs % 86_400) / 3_600,
+        (secs % 3_600) / 60
+    )
+}
+
+impl Handler {
+    /// Access tier of a user; `roles` are the names of their roles in `guild_id`.
+    pub(super) fn role_of(
+        &self,
+        user_id: UserId,
+        guild_id: Option<GuildId>,
+        roles: &[String],
+    ) -> Role {
+        Role::resolve(
+            &self.config.discord,
+            user_id.get(),
+            guild_id.map(|g| g.get()),
+            roles,
+        )
+    }
+
+    /// Access tier of a user from their guild membership, e.g. on an interaction.
+    pub(super) fn member_role(
+        &self,
+        ctx: &Context,
+        user_id: UserId,
+        guild_id: Option<GuildId>,
+        member: Option<&Member>,
+    ) -> Role {
+        let roles = match (guild_id, member) {
+            (Some(guild_id), Some(member)) => role_names(ctx, guild_id, &member.roles),
+            _ => Vec::new(),
+        };
+        self.role_of(user_id, guild_id, &roles)
+    }
+
+    pub(super) fn command_role(&self, ctx: &Context, cmd: &CommandInteraction) -> Role {
+        self.member_role(ctx, cmd.user.id, cmd.guild_id, cmd.member.as_deref())
+    }
+
+    pub(super) async fn handle_admin_command(&self, ctx: &Context, cmd: &CommandInteraction) {
+        let role = self.command_role(ctx, cmd);
+        // Operators manage schedules and see the status; the rest needs an admin.
+        let allowed = match cmd.data.name.as_str() {
+            "schedule" | "status" => role.is_operator(),
+            _ => role.is_admin(),
+        };
+        if !allowed {
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("You don't have permission to use this command.")
+                    .ephemeral(true),
+            );
+            if let Err(e) = cmd.create_response(&ctx.http, response).await {
+                error!("Failed to respond to /{}: {}", cmd.data.name, e);
+            }
+            return;
+        }
+
+        if let Err(e) = cmd.defer_ephemeral(&ctx.http).await {
+            error!("Failed to defer /{}: {}", cmd.data.name, e);
+            return;
+        }
+
+        let reply = match self.run_admin_command(ctx, cmd, role).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                error!("/{} failed: {}", cmd.data.name, e);
+                format!("Command failed: {}", e)
+            }
+        };
+        let reply = split_message(&reply, DISCORD_MAX_LEN)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+
+        if let Err(e) = cmd
+            .edit_response(&ctx.http, EditInteractionResponse::new().content(reply))
+            .await
+        {
+            error!("Failed to respond to /{}: {}", cmd.data.name, e);
+        }
+    }
+
+    async fn run_admin_command(
+        &self,
+        ctx: &Context,
+        cmd: &CommandInteraction,
+        role: Role,
+    ) -> anyhow::Result<String> {
+        let (sub, options) = subcommand(cmd).unwrap_or_default();
+
+        match (cmd.data.name.as_str(), sub) {
+            ("memory", "search") => {
+                let query = string_option(&options, "query").unwrap_or_default();
+                let turns = self
+                    .vector_db
+                    .search_turns(query, MEMORY_SEARCH_RESULTS, &[])
+                    .await?;
+                if turns.is_empty() {
+                    return Ok("No matching conversations.".to_string());
+                }
+                let mut out = String::new();
+                for turn in turns {
+                    let _ = writeln!(
+                        out,
+                        "`{}` **{}**: {}\n> {}",
+                        turn.id,
+                        turn.author,
+                        preview(&turn.user_input),
+                        preview(&turn.assistant_response)
+                    );
+                }
+                Ok(out)
+            }
+            ("memory", "forget") => {
+                let id = string_option(&options, "id").unwrap_or_default();
+                Ok(if self.vector_db.delete_turn(id).await? {
+                    format!("Forgot conversation turn `{}`.", id)
+                } else {
+                    format!("No conversation turn with ID `{}`.", id)
+                })
+            }
+            ("memory", "stats") => {
+                let stats = self.vector_db.stats().await?;
+                Ok(format!(
+                    "**Conversation turns**: {}\n**Indexed vectors**: {}\n**Important facts**: {}",
+                    stats.turns, stats.indexed, stats.important
+                ))
+            }
+            ("important", "list") => {
+                let entries = self.vector_db.list_important().await?;
+                if entries.is_empty() {
+                    return Ok("No important facts saved.".to_string());
+                }
+                let mut out = String::new();
+                for entry in entries {
+                    let _ = writeln!(out, "`{}` {}", entry.id, preview(&entry.content));
+                }
+                Ok(out)
+            }
+            ("important", "add") => {
+                let content = string_option(&options, "content").unwrap_or_default();
+                let id = self.vector_db.add_important(content).await?;
+                Ok(format!("Saved important fact `{}`.", id))
+            }
+            ("important", "delete") => {
+                let id = string_option(&options, "id").unwrap_or_default();
+                Ok(if self.vector_db.delete_important(id).await? {
+                    format!("Deleted important fact `{}`.", id)
+                } else {
+                    format!("No important fact with ID `{}`.", id)
+                })
+            }
+            ("schedule", "list") => {
+                let tasks = self.scheduler.list_tasks().await;
+                if tasks.is_empty() {
+                    return Ok("No scheduled tasks.".to_string());
+                }
+                let mut out = String::new();
+                for task in tasks {
+                    let _ = writeln!(
+                        out,
+                        "`{}` `{}`{}{} {}",
+                        task.id,
+                        task.cron_expr,
+                        task.timezone
+                            .as_deref()
+                            .map(|tz| format!(" ({})", tz))
+                            .unwrap_or_default(),
+                        if task.paused { " ⏸" } else { "" },
+                        preview(if task.description.is_empty() {
+                            &task.prompt
+                        } else {
+                            &task.description
+                        })
+                    );
+                }
+                Ok(out)
+            }
+            ("schedule", "add") => {
+                let cron = string_option(&options, "cron").unwrap_or_default();
+                let prompt = string_option(&options, "prompt").unwrap_or_default();
+                let description = string_option(&options, "description").unwrap_or_default();
+                let timezone = self
+                    .vector_db
+                    .user_preferences(cmd.user.id.get())
+                    .await?
+                    .timezone;
+                let id = self
+                    .scheduler
+                    .add_task(
+                        cron,
+                        prompt,
+                        description,
+                        role,
+                        Some(cmd.channel_id.get()),
+                        timezone,
+                    )
+                    .await?;
+                Ok(format!("Scheduled task `{}`.", id))
+            }
+            ("schedule", "delete") => {
+                let id = string_option(&options, "id").unwrap_or_default();
+                Ok(if self.scheduler.remove_task(id).await? {
+                    format!("Deleted scheduled task `{}`.", id)
+                } else {
+                    format!("No scheduled task with ID `{}`.", id)
+                })
+            }
+            ("schedule", "pause") => {
+                let id = string_option(&options, "id").unwrap_or_default();
+                let resume = bool_option(&options, "resume").unwrap_or(false);
+                Ok(if self.scheduler.set_paused(id, !resume).await? {
+                    format!(
+                        "{} scheduled task `{}`.",
+                        if resume { "Resumed" } else { "Paused" },
+                        id
+                    )
+                } else {
+                    format!("No scheduled task with ID `{}`.", id)
+                })
+            }
+            ("sandbox", "reset") => {
+                RunCommand::reset_container(&self.config).await?;
+                Ok("Sandbox reset. A fresh container is created on the next command.".to_string())
+            }
+            ("sandbox", "status") => {
+                let mut out = String::new();
+                for (name, state) in RunCommand::container_status().await? {
+                    let _ = writeln!(out, "**{}**: {}", name, state);
+                }
+                Ok(out)
+            }
+            ("persona", "list") => {
+                let mut out = String::new();
+                if self.personas.personas().is_empty() {
+                    out.push_str("No personas configured.\n");
+                }
+                for persona in self.personas.personas() {
+                    let _ = writeln!(
+                        out,
+                        "**{}**{} {}",
+                        persona.name,
+                        persona
+                            .model
+                            .as_deref()
+                            .map(|m| format!(" (`{}`)", m))
+                            .unwrap_or_default(),
+                        preview(&persona.instructions)
+                    );
+                }
+                let assignments = self.personas.assignments().await;
+                if !assignments.is_empty() {
+                    out.push_str("\n**Assigned with /persona**\n");
+                }
+                for (id, name) in assignments {
+                    let target = if cmd.guild_id.is_some_and(|g| g.get() == id) {
+                        "this server".to_string()
+                    } else {
+                        format!("<#{}>", id)
+                    };
+                    let _ = writeln!(out, "{}: **{}**", target, name);
+                }
+                let current = self
+                    .personas
+                    .resolve(
+                        Some(&ctx.http),
+                        cmd.guild_id.map(|g| g.get()),
+                        Some(cmd.channel_id.get()),
+                    )
+                    .await
+                    .map_or(DEFAULT_PERSONA, |p| p.name.as_str());
+                let _ = write!(out, "\nActive here: **{}**", current);
+                Ok(out)
+            }
+            ("persona", "set" | "clear") => {
+                let server = bool_option(&options, "server").unwrap_or(false);
+                let (target, place) = match cmd.guild_id {
+                    Some(guild_id) if server => (guild_id.get(), "this server"),
+                    None if server => return Ok("There is no server here.".to_string()),
+                    _ => (cmd.channel_id.get(), "this channel"),
+                };
+                if sub == "set" {
+                    let name = string_option(&options, "name").unwrap_or_default();
+                    self.personas.assign(target, Some(name)).await?;
+                    info!("Persona for {} set to {} by {}", target, name, cmd.user.id);
+                    Ok(format!("Set the **{}** persona for {}.", name, place))
+                } else if self.personas.assign(target, None).await? {
+                    Ok(format!("Cleared the persona set for {}.", place))
+                } else {
+                    Ok(format!("No persona was set for {} with /persona.", place))
+                }
+            }
+            ("status", _) => {
+                let stats = self.vector_db.stats().await?;
+                let tasks = self.scheduler.list_tasks().await;
+                let paused = tasks.iter().filter(|t| t.paused).count();
+                Ok(format!(
+                    "**Version**: {}\n**Uptime**: {}\n**Model**: {} ({})\n\
+                     **Active streams**: {} ({} queued)\n**Scheduled tasks**: {} ({} paused)\n\
+                     **Memory**: {} turns, {} important facts",
+                    env!("CARGO_PKG_VERSION"),
+                    format_uptime(self.started.elapsed()),
+                    self.config.api.model,
+                    self.config.api.provider,
+                    self.active_streams.lock().await.len(),
+                    self.queue.queued(),
+                    tasks.len(),
+                    paused,
+                    stats.turns,
+                    stats.important
+                ))
+            }
+            (name, sub) => Ok(format!("Unknown command: /{} {}", name, sub)),
+        }
+    }
+}
+
+pub(super) async fn register_commands(ctx: &Context) {
+    match serenity::model::application::Command::set_global_commands(
+        &ctx.http,
+        command_definitions(),
+    )
+    .await
+    {
+        Ok(commands) => info!("Registered {} slash commands", commands.len()),
+        Err(e) => error!("Failed to register slash commands: {}", e),
+    }
+}
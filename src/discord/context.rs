@@ -0,0 +1,203 @@
+use super::*;
+use crate::config::{ContextConfig, ThreadsConfig};
+use serenity::{
+    builder::GetMessages,
+    model::{
+        channel::{GuildChannel, Message},
+        id::{MessageId, UserId},
+    },
+};
+use std::fmt::Write;
+use tracing::warn;
+
+const MAX_MESSAGE_CHARS: usize = 1000;
+
+fn format_message(out: &mut String, msg: &Message, bot_id: UserId) {
+    let ts = chrono::DateTime::from_timestamp(msg.timestamp.unix_timestamp(), 0)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let author = if msg.author.id == bot_id {
+        "RustClaw (you)"
+    } else {
+        msg.author
+            .global_name
+            .as_deref()
+            .unwrap_or(&msg.author.name)
+    };
+
+    let content = msg.content.replace(&format!("<@{}>", bot_id), "@RustClaw");
+    let mut content: String = content.trim().chars().take(MAX_MESSAGE_CHARS).collect();
+    if msg.content.chars().count() > MAX_MESSAGE_CHARS {
+        content.push('…');
+    }
+
+    let _ = write!(out, "[{}] {}: {}", ts, author, content);
+    if !msg.attachments.is_empty() {
+        let names: Vec<&str> = msg
+            .attachments
+            .iter()
+            .map(|a| a.filename.as_str())
+            .collect();
+        let _ = write!(out, " [attachments: {}]", names.join(", "));
+    }
+    out.push('\n');
+}
+
+/// Formats messages (oldest first), dropping the oldest ones once the
+/// remaining character budget is exhausted.
+fn take_newest(messages: &[Message], bot_id: UserId, budget: &mut usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for m in messages.iter().rev() {
+        let mut line = String::new();
+        format_message(&mut line, m, bot_id);
+        let len = line.chars().count();
+        if len > *budget {
+            break;
+        }
+        *budget -= len;
+        lines.push(line);
+    }
+    lines.reverse();
+    lines
+}
+
+/// Follows the replies above `msg`. The chain stops at a message from
+/// another channel (e.g. a forward), which the requester may not be able to see.
+async fn resolve_reply_chain(ctx: &Context, msg: &Message, depth: usize) -> Vec<Message> {
+    let mut chain = Vec::new();
+    let same_channel = |m: &Message| m.channel_id == msg.channel_id;
+    let mut next = msg
+        .referenced_message
+        .as_deref()
+        .filter(|m| same_channel(m))
+        .cloned();
+
+    while let Some(current) = next.take() {
+        if chain.len() >= depth {
+            break;
+        }
+        next = match (&current.referenced_message, &current.message_reference) {
+            (Some(parent), _) => Some(parent.as_ref().clone()).filter(same_channel),
+            (None, Some(reference)) if reference.channel_id != msg.channel_id => None,
+            (None, Some(reference)) => match reference.message_id {
+                Some(id) => match reference.channel_id.message(&ctx.http, id).await {
+                    Ok(parent) => Some(parent),
+                    Err(e) => {
+                        warn!("Failed to fetch referenced message {}: {}", id, e);
+                        None
+                    }
+                },
+                None => None,
+            },
+            (None, None) => None,
+        };
+        chain.push(current);
+    }
+
+    chain.reverse();
+    chain
+}
+
+/// Builds the `[Discord Context]` prompt section from the reply chain and
+/// preceding channel messages, trimmed to the configured character budget.
+pub(super) async fn build_discord_context(
+    ctx: &Context,
+    msg: &Message,
+    bot_id: UserId,
+    config: &ContextConfig,
+) -> String {
+    let chain = if config.reply_depth > 0 {
+        resolve_reply_chain(ctx, msg, config.reply_depth).await
+    } else {
+        Vec::new()
+    };
+
+    let recent = if config.recent_messages > 0 {
+        let limit = config.recent_messages.min(100);
+        match msg
+            .channel_id
+            .messages(&ctx.http, GetMessages::new().before(msg.id).limit(limit))
+            .await
+        {
+            Ok(mut messages) => {
+                messages.retain(|m| !chain.iter().any(|c| c.id == m.id));
+                messages.reverse();
+                messages
+            }
+            Err(e) => {
+                warn!("Failed to fetch recent channel messages: {}", e);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    if chain.is_empty() && recent.is_empty() {
+        return String::new();
+    }
+
+    let mut budget = config.max_chars;
+    let keep_chain = take_newest(&chain, bot_id, &mut budget);
+    let keep_recent = take_newest(&recent, bot_id, &mut budget);
+
+    if keep_chain.is_empty() && keep_recent.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("[Discord Context]\n");
+    if !keep_recent.is_empty() {
+        out.push_str("Recent channel messages (oldest first):\n");
+        out.extend(keep_recent);
+    }
+    if !keep_chain.is_empty() {
+        out.push_str("The user is replying to this message chain (oldest first):\n");
+        out.extend(keep_chain);
+    }
+    out
+}
+
+/// Builds the `[Discord Context]` section for a follow-up in a bot-owned
+/// thread from the thread history, including the message it was started on.
+pub(super) async fn build_thread_context(
+    ctx: &Context,
+    msg: &Message,
+    thread: &GuildChannel,
+    bot_id: UserId,
+    config: &ThreadsConfig,
+) -> String {
+    let limit = config.history_messages.clamp(1, 100);
+    let mut history = match thread
+        .id
+        .messages(&ctx.http, GetMessages::new().before(msg.id).limit(limit))
+        .await
+    {
+        Ok(messages) => messages,
+        Err(e) => {
+            warn!("Failed to fetch thread history: {}", e);
+            Vec::new()
+        }
+    };
+    history.reverse();
+
+    // Threads started on a message share its ID.
+    if history.len() < limit as usize
+        && let Some(parent_id) = thread.parent_id
+        && let Ok(starter) = parent_id
+            .message(&ctx.http, MessageId::new(thread.id.get()))
+            .await
+    {
+        history.insert(0, starter);
+    }
+
+    let mut budget = config.max_chars;
+    let lines = take_newest(&history, bot_id, &mut budget);
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("[Discord Context]\n");
+    out.push_str("This conversation is a thread; messages so far (oldest first):\n");
+    out.extend(lines);
+    out
+}
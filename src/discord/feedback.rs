@@ -0,0 +1,312 @@
+use super::*;
+use crate::{agent::AgentResponse, vector_db::FeedbackEntry};
+use serde::Serialize;
+use serenity::{
+    all::{ButtonStyle, CreateButton},
+    builder::{
+        CreateAttachment, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+        CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+        EditInteractionResponse,
+    },
+    model::application::{CommandInteraction, CommandOptionType, ComponentInteraction},
+};
+use std::{collections::BTreeMap, fmt::Write};
+use tracing::{error, info};
+
+const CUSTOM_ID_PREFIX: &str = "feedback";
+const DEFAULT_DAYS: i64 = 30;
+const MAX_DAYS: i64 = 365;
+
+pub(super) fn feedback_command() -> CreateCommand {
+    let days = || {
+        CreateCommandOption::new(
+            CommandOptionType::Integer,
+            "days",
+            "How many days back to look (default 30)",
+        )
+        .min_int_value(1)
+        .max_int_value(MAX_DAYS as u64)
+    };
+    CreateCommand::new("feedback")
+        .description("(Admin only) Review answer ratings")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "report", "Ratings per model")
+                .add_sub_option(days()),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "export",
+                "Download 👎 answers as JSONL",
+            )
+            .add_sub_option(days()),
+        )
+}
+
+/// 👍/👎 buttons for a stored answer.
+pub(super) fn feedback_buttons(turn_id: &str) -> [CreateButton; 2] {
+    [
+        CreateButton::new(format!("{}:up:{}", CUSTOM_ID_PREFIX, turn_id))
+            .emoji('👍')
+            .style(ButtonStyle::Secondary),
+        CreateButton::new(format!("{}:down:{}", CUSTOM_ID_PREFIX, turn_id))
+            .emoji('👎')
+            .style(ButtonStyle::Secondary),
+    ]
+}
+
+/// Parses a feedback button into `(rating, turn_id)`.
+fn parse_custom_id(custom_id: &str) -> Option<(i32, &str)> {
+    let mut parts = custom_id.splitn(3, ':');
+    if parts.next()? != CUSTOM_ID_PREFIX {
+        return None;
+    }
+    let rating = match parts.next()? {
+        "up" => 1,
+        "down" => -1,
+        _ => return None,
+    };
+    let turn_id = parts.next().filter(|id| !id.is_empty())?;
+    Some((rating, turn_id))
+}
+
+fn message_link(entry: &FeedbackEntry) -> String {
+    let guild = entry
+        .guild_id
+        .map_or_else(|| "@me".to_string(), |g| g.to_string());
+    format!(
+        "https://discord.com/channels/{}/{}/{}",
+        guild, entry.channel_id, entry.message_id
+    )
+}
+
+/// One line of the `/feedback export` file.
+#[derive(Serialize)]
+struct ExportedTurn<'a> {
+    turn_id: &'a str,
+    timestamp: String,
+    author: &'a str,
+    input: &'a str,
+    response: &'a str,
+    model: &'a str,
+    total_tokens: u64,
+    link: String,
+}
+
+#[derive(Default)]
+struct ModelStats {
+    answers: usize,
+    up: usize,
+    down: usize,
+    tokens: u64,
+}
+
+fn report(entries: &[FeedbackEntry], days: i64) -> String {
+    if entries.is_empty() {
+        return format!("No answers logged in the last {} days.", days);
+    }
+
+    let mut models: BTreeMap<&str, ModelStats> = BTreeMap::new();
+    for entry in entries {
+        let stats = models.entry(entry.model.as_str()).or_default();
+        stats.answers += 1;
+        stats.tokens += entry.total_tokens;
+        match entry.rating {
+            Some(r) if r > 0 => stats.up += 1,
+            Some(r) if r < 0 => stats.down += 1,
+            _ => {}
+        }
+    }
+
+    let up: usize = models.values().map(|s| s.up).sum();
+    let down: usize = models.values().map(|s| s.down).sum();
+    let mut out = format!(
+        "**Last {} days**: {} answers, {} rated (👍 {} / 👎 {})\n",
+        days,
+        entries.len(),
+        up + down,
+        up,
+        down
+    );
+    for (model, stats) in &models {
+        let rated = stats.up + stats.down;
+        let approval = if rated == 0 {
+            "–".to_string()
+        } else {
+            format!("{:.0}%", stats.up as f64 * 100.0 / rated as f64)
+        };
+        let _ = writeln!(
+            out,
+            "`{}`: {} answers, 👍 {} / 👎 {} ({} positive), {} tokens on average",
+            model,
+            stats.answers,
+            stats.up,
+            stats.down,
+            approval,
+            stats.tokens / stats.answers as u64
+        );
+    }
+    out
+}
+
+impl Handler {
+    /// Logs a finished answer so its buttons can rate it.
+    pub(super) async fn record_answer(
+        &self,
+        request: &ReplyRequest,
+        response: &AgentResponse,
+        relay: &StreamRelay,
+    ) {
+        if !self.config.feedback.enabled || response.turn_id.is_empty() {
+            return;
+        }
+        let entry = FeedbackEntry {
+            turn_id: response.turn_id.clone(),
+            message_id: relay.cancel_msg_id.get(),
+            channel_id: request.channel_id.get(),
+            guild_id: request.guild_id.map(|g| g.get()),
+            user_id: request.requester_id.get(),
+            model: response.model.clone(),
+            total_tokens: response.total_tokens,
+            rating: None,
+            timestamp_micros: 0,
+        };
+        if let Err(e) = self.vector_db.record_answer(entry).await {
+            error!("Failed to log answer {}: {}", response.turn_id, e);
+        }
+    }
+
+    pub(super) async fn handle_feedback_button(
+        &self,
+        ctx: &Context,
+        component: &ComponentInteraction,
+    ) {
+        let Some((rating, turn_id)) = parse_custom_id(&component.data.custom_id) else {
+            return;
+        };
+
+        let is_admin = self
+            .member_role(
+                ctx,
+                component.user.id,
+                component.guild_id,
+                component.member.as_ref(),
+            )
+            .is_admin();
+        let reply = match self.vector_db.feedback(turn_id).await {
+            Ok(None) => "This answer can no longer be rated.",
+            Ok(Some(entry)) if component.user.id.get() != entry.user_id && !is_admin => {
+                "Only the person who asked can rate this answer."
+            }
+            Ok(Some(_)) => match self.vector_db.set_rating(turn_id, rating).await {
+                Ok(_) => {
+                    info!(
+                        "Answer {} rated {} by {}",
+                        turn_id, rating, component.user.id
+                    );
+                    "Thanks for the feedback!"
+                }
+                Err(e) => {
+                    error!("Failed to save feedback for {}: {}", turn_id, e);
+                    "Couldn't save your feedback."
+                }
+            },
+            Err(e) => {
+                error!("Failed to look up feedback for {}: {}", turn_id, e);
+                "Couldn't save your feedback."
+            }
+        };
+
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(reply)
+                .ephemeral(true),
+        );
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            error!("Failed to respond to feedback button: {}", e);
+        }
+    }
+
+    pub(super) async fn handle_feedback_command(&self, ctx: &Context, cmd: &CommandInteraction) {
+        if !self.command_role(ctx, cmd).is_admin() {
+            return self
+                .respond_ephemeral(ctx, cmd, "You don't have permission to use this command.")
+                .await;
+        }
+        if let Err(e) = cmd.defer_ephemeral(&ctx.http).await {
+            error!("Failed to defer /feedback: {}", e);
+            return;
+        }
+
+        let (sub, options) = subcommand(cmd).unwrap_or_default();
+        let days = int_option(&options, "days")
+            .unwrap_or(DEFAULT_DAYS)
+            .clamp(1, MAX_DAYS);
+        let since = chrono::Utc::now().timestamp_micros() - days * 86_400_000_000;
+
+        let result = match sub {
+            "export" => self.export_feedback(ctx, cmd, since, days).await,
+            _ => match self.vector_db.feedback_since(since).await {
+                Ok(entries) => Ok(report(&entries, days)),
+                Err(e) => Err(e),
+            },
+        };
+        let reply = result.unwrap_or_else(|e| {
+            error!("/feedback {} failed: {}", sub, e);
+            format!("Command failed: {}", e)
+        });
+
+        if let Err(e) = cmd
+            .edit_response(&ctx.http, EditInteractionResponse::new().content(reply))
+            .await
+        {
+            error!("Failed to respond to /feedback: {}", e);
+        }
+    }
+
+    /// Sends the 👎 answers of the last `days` as a follow-up file.
+    async fn export_feedback(
+        &self,
+        ctx: &Context,
+        cmd: &CommandInteraction,
+        since: i64,
+        days: i64,
+    ) -> anyhow::Result<String> {
+        let rows = self.vector_db.downvoted_turns(since).await?;
+        if rows.is_empty() {
+            return Ok(format!("No 👎 answers in the last {} days.", days));
+        }
+
+        let mut jsonl = String::new();
+        for (entry, turn) in &rows {
+            let line = ExportedTurn {
+                turn_id: &entry.turn_id,
+                timestamp: chrono::DateTime::from_timestamp_micros(entry.timestamp_micros)
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default(),
+                author: &turn.author,
+                input: &turn.user_input,
+                response: &turn.assistant_response,
+                model: &entry.model,
+                total_tokens: entry.total_tokens,
+                link: message_link(entry),
+            };
+            jsonl.push_str(&serde_json::to_string(&line)?);
+            jsonl.push('\n');
+        }
+
+        let file = CreateAttachment::bytes(jsonl.into_bytes(), "feedback.jsonl");
+        cmd.create_followup(
+            &ctx.http,
+            CreateInteractionResponseFollowup::new()
+                .add_file(file)
+                .ephemeral(true),
+        )
+        .await?;
+        Ok(format!(
+            "Exported {} 👎 answers from the last {} days.",
+            rows.len(),
+            days
+        ))
+    }
+}
@@ -1,21 +1,24 @@
 use super::*;
 use crate::{
-    agent::{Agent, AttachmentInfo, StreamEvent, UserInfo},
-    config::Config,
+    agent::{Agent, AgentResponse, AttachmentInfo, RequestContext, StreamEvent, UserInfo},
+    approval::ApprovalGate,
+    config::{Config, DisplayConfig, ThreadMode},
     scheduler::Scheduler,
+    vector_db::VectorDb,
 };
 use serenity::{
     all::{CreateAttachment, Http},
     async_trait,
     builder::{
-        CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
-        EditMessage,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, EditMessage,
     },
     model::{
-        application::Interaction,
+        application::{CommandInteraction, ComponentInteraction, Interaction},
         channel::{Message, Reaction},
+        event::MessageUpdateEvent,
         gateway::Ready,
-        id::{ChannelId, MessageId, UserId},
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        user::User,
     },
 };
 use std::{
@@ -23,27 +26,43 @@ use std::{
     path::PathBuf,
     sync::{
         Arc,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
 };
 use tokio::sync::{Mutex, mpsc};
 use tracing::{error, info, warn};
 
+const ATTACHMENTS_ONLY_PROMPT: &str = "I've attached files. Please check them.";
+
 pub(super) struct StreamControl {
     pub abort_handle: tokio::task::AbortHandle,
     pub cancelled: Arc<AtomicBool>,
     pub requester_id: UserId,
     pub channel_id: ChannelId,
+    pub source_id: MessageId,
+    pub superseded: Arc<AtomicBool>,
+    pub interrupted: Arc<AtomicBool>,
 }
 
 pub(super) struct Handler {
     pub agent: Arc<dyn Agent>,
     pub config: Config,
     pub bot_id: Arc<RwLock<Option<UserId>>>,
-    pub owner_id: UserId,
     pub scheduler: Arc<Scheduler>,
+    pub approvals: Arc<ApprovalGate>,
+    pub vector_db: Arc<VectorDb>,
+    pub personas: Arc<PersonaStore>,
+    pub thread_parents: Arc<ThreadParents>,
+    pub started: std::time::Instant,
     pub http_client: reqwest::Client,
     pub active_streams: Arc<Mutex<HashMap<MessageId, StreamControl>>>,
+    pub pacer: Arc<EditPacer>,
+    pub limiter: RequestLimiter,
+    pub queue: RequestQueue,
+    pub sessions: Mutex<ReplySessions>,
+    pub shutting_down: AtomicBool,
+    pub running_streams: AtomicUsize,
+    pub interrupted: Mutex<Vec<InterruptedReply>>,
 }
 
 async fn send_pending_file(
@@ -72,15 +91,33 @@ pub async fn send_agent_response(
     http: &Http,
     channel_id: u64,
     response: &crate::agent::AgentResponse,
+    display: &DisplayConfig,
 ) {
     let channel = ChannelId::new(channel_id);
 
-    for chunk in split_message(&response.text, DISCORD_MAX_LEN) {
-        if let Err(e) = channel.say(http, &chunk).await {
-            error!(
-                "Failed to send response chunk to Discord channel {}: {}",
-                channel_id, e
-            );
+    if needs_long_response(&response.text, display) {
+        let parts = long_response_parts(
+            &response.text,
+            None,
+            display.overflow,
+            display.overflow_after,
+        );
+        for part in parts {
+            if let Err(e) = channel.send_message(http, part.create()).await {
+                error!(
+                    "Failed to send response to Discord channel {}: {}",
+                    channel_id, e
+                );
+            }
+        }
+    } else {
+        for chunk in split_message(&response.text, DISCORD_MAX_LEN) {
+            if let Err(e) = channel.say(http, &chunk).await {
+                error!(
+                    "Failed to send response chunk to Discord channel {}: {}",
+                    channel_id, e
+                );
+            }
         }
     }
 
@@ -94,33 +131,60 @@ pub async fn send_agent_response(
     }
 }
 
+/// Names of the given roles in `guild_id`, from the cache.
+pub(super) fn role_names(ctx: &Context, guild_id: GuildId, role_ids: &[RoleId]) -> Vec<String> {
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return Vec::new();
+    };
+    role_ids
+        .iter()
+        .filter_map(|role_id| guild.roles.get(role_id).map(|r| r.name.clone()))
+        .collect()
+}
+
+/// Describes a user for the prompt; `member` is their nickname and roles in
+/// `guild_id`.
+pub(super) fn user_info_for(
+    ctx: &Context,
+    author: &User,
+    guild_id: Option<GuildId>,
+    member: Option<(Option<&str>, &[RoleId])>,
+) -> UserInfo {
+    let mut user_info = UserInfo {
+        name: author.name.clone(),
+        global_name: author.global_name.clone(),
+        id: author.id.get(),
+        avatar_url: author.avatar_url(),
+        ..Default::default()
+    };
+
+    if let Some(guild_id) = guild_id
+        && let Some((nick, roles)) = member
+    {
+        user_info.nickname = nick.map(str::to_string);
+        user_info.roles = role_names(ctx, guild_id, roles);
+    }
+
+    user_info
+}
+
 impl Handler {
     pub(super) async fn build_user_info(&self, ctx: &Context, msg: &Message) -> UserInfo {
-        let author = &msg.author;
-
-        let mut user_info = UserInfo {
-            name: author.name.clone(),
-            global_name: author.global_name.clone(),
-            id: author.id.get(),
-            avatar_url: author.avatar_url(),
-            ..Default::default()
-        };
+        let member = msg
+            .member
+            .as_ref()
+            .map(|m| (m.nick.as_deref(), m.roles.as_slice()));
+        let mut user_info = user_info_for(ctx, &msg.author, msg.guild_id, member);
+        self.load_preferences(&mut user_info).await;
+        user_info
+    }
 
-        if let Some(guild_id) = msg.guild_id
-            && let Some(ref member) = msg.member
-        {
-            user_info.nickname = member.nick.clone();
-
-            if let Some(guild) = ctx.cache.guild(guild_id) {
-                user_info.roles = member
-                    .roles
-                    .iter()
-                    .filter_map(|role_id| guild.roles.get(role_id).map(|r| r.name.clone()))
-                    .collect();
-            }
+    /// Adds the user's stored preferences to `user_info`.
+    pub(super) async fn load_preferences(&self, user_info: &mut UserInfo) {
+        match self.vector_db.user_preferences(user_info.id).await {
+            Ok(preferences) => user_info.preferences = preferences,
+            Err(e) => warn!("Failed to load preferences of {}: {}", user_info.id, e),
         }
-
-        user_info
     }
 
     pub(super) async fn download_attachments(&self, msg: &Message) -> Vec<AttachmentInfo> {
@@ -168,6 +232,7 @@ impl Handler {
                         container_path: format!("/workspace/upload/{}", final_filename),
                         size: attachment.size,
                         content_type: attachment.content_type.clone(),
+                        host_path,
                     });
                 }
                 Err(e) => {
@@ -208,13 +273,6 @@ impl Handler {
             .join("upload")
     }
 
-    fn extract_content(msg: &Message, bot_id: UserId) -> String {
-        msg.content
-            .replace(&format!("<@{}>", bot_id), "")
-            .trim()
-            .to_string()
-    }
-
     async fn run_stream_loop(
         &self,
         ctx: &Context,
@@ -223,19 +281,33 @@ impl Handler {
         origin_channel: ChannelId,
     ) {
         loop {
-            match tokio::time::timeout(STREAM_POLL_TIMEOUT, rx.recv()).await {
+            // Wake up when a skipped edit can go through, even if no events
+            // arrive (e.g. during a long tool call).
+            let wait = relay
+                .pending_flush()
+                .map_or(STREAM_POLL_TIMEOUT, |ready| ready.min(STREAM_POLL_TIMEOUT));
+            match tokio::time::timeout(wait, rx.recv()).await {
                 Ok(Some(StreamEvent::TextDelta(text))) => {
                     relay
                         .push_delta(ctx, &text, origin_channel, &self.active_streams)
                         .await;
                 }
+                Ok(Some(StreamEvent::ReasoningDelta(text))) => {
+                    relay.push_reasoning(ctx, &text).await;
+                }
+                Ok(Some(StreamEvent::ToolCallStart { id, name, summary })) => {
+                    relay.push_tool_start(ctx, id, name, summary).await;
+                }
+                Ok(Some(StreamEvent::ToolCallEnd { id, success })) => {
+                    relay.push_tool_end(ctx, &id, success).await;
+                }
                 Ok(Some(StreamEvent::Done)) | Ok(None) => break,
                 Ok(Some(StreamEvent::Error(e))) => {
                     error!("Stream error: {}", e);
                     break;
                 }
                 Err(_) => {
-                    if relay.last_edit.elapsed() >= EDIT_INTERVAL && !relay.accumulated.is_empty() {
+                    if relay.pending_flush().is_some() {
                         relay.flush_edit(ctx).await;
                     }
                 }
@@ -243,32 +315,339 @@ impl Handler {
         }
     }
 
-    async fn send_response_files_and_notify(
+    pub(super) async fn send_response_files_and_notify(
         &self,
         ctx: &Context,
-        msg: &Message,
+        channel_id: ChannelId,
+        user_id: UserId,
         relay: &mut StreamRelay,
-        response: crate::agent::AgentResponse,
+        response: &crate::agent::AgentResponse,
     ) {
-        if relay.accumulated.is_empty() && !response.text.is_empty() {
+        let display = &self.config.display;
+        if relay.accumulated.is_empty() && needs_long_response(&response.text, display) {
+            let parts = long_response_parts(
+                &response.text,
+                None,
+                display.overflow,
+                display.overflow_after,
+            );
+            self.pacer
+                .edit(ctx, &mut relay.reply_msg, parts[0].edit())
+                .await;
+            for part in &parts[1..] {
+                let _ = channel_id.send_message(&ctx.http, part.create()).await;
+            }
+        } else if relay.accumulated.is_empty() && !response.text.is_empty() {
             let chunks = split_message(&response.text, DISCORD_MAX_LEN);
-            let _ = relay
-                .reply_msg
-                .edit(ctx, EditMessage::new().content(&chunks[0]))
+            self.pacer
+                .edit(
+                    ctx,
+                    &mut relay.reply_msg,
+                    EditMessage::new().content(&chunks[0]),
+                )
                 .await;
             for chunk in &chunks[1..] {
-                let _ = msg.channel_id.say(&ctx.http, chunk).await;
+                let _ = channel_id.say(&ctx.http, chunk).await;
             }
         }
 
         for file in &response.files {
-            if let Err(e) = send_pending_file(&ctx.http, msg.channel_id, file).await {
+            if let Err(e) = send_pending_file(&ctx.http, channel_id, file).await {
                 error!("Failed to send file '{}': {}", file.filename, e);
             }
         }
 
-        self.send_ephemeral_mention(ctx, msg.channel_id, msg.author.id)
-            .await;
+        self.send_ephemeral_mention(ctx, channel_id, user_id).await;
+    }
+
+    /// Runs the agent for a request and streams the answer into `relay`.
+    /// When the source message is edited mid-stream, the run restarts with
+    /// the edited text in the same messages. Returns `None` when the run was
+    /// cancelled or failed; the reply message already says so.
+    pub(super) async fn stream_reply(
+        &self,
+        ctx: &Context,
+        mut request: Arc<ReplyRequest>,
+        mut relay: StreamRelay,
+        options: RunOptions,
+    ) -> Option<(Arc<ReplyRequest>, AgentResponse, StreamRelay)> {
+        let _running = self.track_stream();
+        let _pace = self.pacer.join(relay.reply_msg.channel_id);
+        let _slot = self
+            .wait_for_turn(ctx, &request, &mut relay, &options)
+            .await?;
+        let _permit = match self.occupy(&request) {
+            Ok(permit) => permit,
+            Err(e) => {
+                info!("Rejected request from {}: {}", request.requester_id, e);
+                let _ = ctx
+                    .http
+                    .delete_message_reaction_emoji(
+                        request.channel_id,
+                        relay.cancel_msg_id,
+                        &cancel_emoji(),
+                    )
+                    .await;
+                relay.finalize_with_note(ctx, &e.to_string()).await;
+                return None;
+            }
+        };
+        loop {
+            let (tx, mut rx) = mpsc::channel::<StreamEvent>(128);
+            let agent = self.agent.clone();
+            let job = request.clone();
+            let run = options.clone();
+            let handle = tokio::spawn(async move {
+                agent
+                    .process_streaming(
+                        &job.input,
+                        RequestContext {
+                            role: job.role,
+                            discord_channel_id: Some(job.channel_id.get()),
+                            guild_id: job.guild_id.map(|g| g.get()),
+                            user_info: Some(&job.user_info),
+                            attachments: &job.attachments,
+                            discord_context: &job.discord_context,
+                            max_turns: job.max_turns,
+                            replace_turn: run.replace_turn.as_deref(),
+                            previous_response: run.previous_response.as_deref(),
+                            model: run.model.as_deref(),
+                            thread_history: job.thread_history,
+                        },
+                        tx,
+                    )
+                    .await
+            });
+
+            let _ = relay.reply_msg.react(ctx, CANCEL_EMOJI).await;
+
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let superseded = Arc::new(AtomicBool::new(false));
+            let interrupted = Arc::new(AtomicBool::new(false));
+            self.active_streams.lock().await.insert(
+                relay.cancel_msg_id,
+                StreamControl {
+                    abort_handle: handle.abort_handle(),
+                    cancelled: cancelled.clone(),
+                    requester_id: request.requester_id,
+                    channel_id: request.channel_id,
+                    source_id: request.source_id,
+                    superseded: superseded.clone(),
+                    interrupted: interrupted.clone(),
+                },
+            );
+
+            self.run_stream_loop(ctx, &mut relay, &mut rx, request.channel_id)
+                .await;
+
+            self.unregister_stream(ctx, &relay, request.channel_id)
+                .await;
+
+            if cancelled.load(Ordering::Acquire) {
+                self.record_estimated_usage(&request, &relay);
+                if interrupted.load(Ordering::Acquire) {
+                    self.checkpoint(ctx, &request, &mut relay, &options).await;
+                    return None;
+                }
+                if superseded.load(Ordering::Acquire)
+                    && let Some(edited) = self.edited_request(ctx, &request).await
+                    && let Some(next) = StreamRelay::reuse(
+                        relay.messages(),
+                        &self.config.display,
+                        self.pacer.clone(),
+                    )
+                {
+                    if !request.role.is_admin()
+                        && let Err(e) = self.limiter.admit(request.requester_id, request.guild_id)
+                    {
+                        info!("Not restarting reply to {}: {}", request.source_id, e);
+                        relay.finalize_with_note(ctx, &e.to_string()).await;
+                        return None;
+                    }
+                    info!("Restarting reply to edited message {}", request.source_id);
+                    request = Arc::new(edited);
+                    relay = next;
+                    let _ = relay
+                        .reply_msg
+                        .edit(ctx, EditMessage::new().content("…"))
+                        .await;
+                    continue;
+                }
+                relay.finalize(ctx, true).await;
+                return None;
+            }
+
+            relay.finalize(ctx, false).await;
+
+            match handle.await {
+                Ok(Ok(response)) => {
+                    self.limiter.record_usage(
+                        request.requester_id,
+                        request.guild_id,
+                        response.total_tokens,
+                    );
+                    self.record_answer(&request, &response, &relay).await;
+                    return Some((request, response, relay));
+                }
+                Ok(Err(e)) => error!("Agent error: {}", e),
+                Err(e) if e.is_cancelled() => {
+                    self.record_estimated_usage(&request, &relay);
+                    return None;
+                }
+                Err(e) => error!("Task join error: {}", e),
+            }
+            self.record_estimated_usage(&request, &relay);
+
+            self.pacer
+                .edit(
+                    ctx,
+                    &mut relay.reply_msg,
+                    EditMessage::new().content("An error occurred while processing."),
+                )
+                .await;
+            return None;
+        }
+    }
+
+    /// Counts a run that ended without reporting its usage (cancelled, failed
+    /// or interrupted) against the budgets, estimated from what it was given
+    /// and what it streamed.
+    fn record_estimated_usage(&self, request: &ReplyRequest, relay: &StreamRelay) {
+        self.limiter.record_usage(
+            request.requester_id,
+            request.guild_id,
+            estimate_tokens(
+                request.input.len() + request.discord_context.len() + relay.streamed_chars(),
+            ),
+        );
+    }
+
+    /// Takes the concurrency slots of a request whose turn has come. Admins
+    /// are not limited.
+    pub(super) fn occupy(&self, request: &ReplyRequest) -> Result<Option<LimitPermit>, LimitError> {
+        if request.role.is_admin() {
+            return Ok(None);
+        }
+        self.limiter
+            .occupy(request.requester_id, request.channel_id)
+            .map(Some)
+    }
+
+    /// Waits until no other request in the same queue is running, showing the
+    /// queue position in an empty placeholder. Returns `None` if the request
+    /// was cancelled with the ❌ reaction or by a shutdown while waiting.
+    async fn wait_for_turn(
+        &self,
+        ctx: &Context,
+        request: &ReplyRequest,
+        relay: &mut StreamRelay,
+        options: &RunOptions,
+    ) -> Option<QueueSlot> {
+        let mut ticket = match self.queue.join(
+            relay.cancel_msg_id,
+            request.requester_id,
+            request.channel_id,
+            request.guild_id,
+        ) {
+            Admission::Ready(slot) => return Some(slot),
+            Admission::Queued(ticket) => ticket,
+        };
+
+        info!(
+            "Queued reply {} at position {}",
+            relay.cancel_msg_id,
+            ticket.position()
+        );
+        let _ = relay.reply_msg.react(ctx, CANCEL_EMOJI).await;
+        let show_position = relay.is_empty();
+        let mut position = ticket.position();
+        loop {
+            if show_position {
+                let _ = relay
+                    .reply_msg
+                    .edit(
+                        ctx,
+                        EditMessage::new().content(format!("⏳ Queued (position {})", position)),
+                    )
+                    .await;
+            }
+            match ticket.next().await {
+                QueueUpdate::Position(next) => position = next,
+                // A turn that comes during shutdown isn't taken: no new runs
+                // start in the grace period.
+                QueueUpdate::Ready(slot) if !self.is_shutting_down() => {
+                    if show_position {
+                        let _ = relay
+                            .reply_msg
+                            .edit(ctx, EditMessage::new().content("…"))
+                            .await;
+                    }
+                    return Some(slot);
+                }
+                QueueUpdate::Ready(_) | QueueUpdate::Cancelled => {
+                    let _ = ctx
+                        .http
+                        .delete_message_reaction_emoji(
+                            request.channel_id,
+                            relay.cancel_msg_id,
+                            &cancel_emoji(),
+                        )
+                        .await;
+                    if self.is_shutting_down() {
+                        self.checkpoint(ctx, request, relay, options).await;
+                    } else {
+                        relay.finalize(ctx, true).await;
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Re-reads the source message of a request and returns the request with
+    /// its edited text, or `None` if the message no longer triggers the bot.
+    pub(super) async fn edited_request(
+        &self,
+        ctx: &Context,
+        request: &ReplyRequest,
+    ) -> Option<ReplyRequest> {
+        let bot_id = (*self.bot_id.read().await)?;
+        let msg = request
+            .source_channel
+            .unwrap_or(request.channel_id)
+            .message(&ctx.http, request.source_id)
+            .await
+            .ok()?;
+        let parent_id = self.parent_channel(ctx, msg.guild_id, msg.channel_id).await;
+        let content = match_trigger(
+            &self.config.triggers,
+            &msg,
+            parent_id,
+            bot_id,
+            request.thread_history,
+        )?;
+        let input = if !content.is_empty() {
+            content
+        } else if !request.attachments.is_empty() {
+            ATTACHMENTS_ONLY_PROMPT.to_string()
+        } else {
+            return None;
+        };
+        let discord_context = if request.thread_history {
+            match current_thread(ctx, &msg).await {
+                Some(thread) => {
+                    build_thread_context(ctx, &msg, &thread, bot_id, &self.config.threads).await
+                }
+                None => request.discord_context.clone(),
+            }
+        } else {
+            build_discord_context(ctx, &msg, bot_id, &self.config.context).await
+        };
+        Some(ReplyRequest {
+            input,
+            discord_context,
+            ..request.clone()
+        })
     }
 
     async fn send_ephemeral_mention(&self, ctx: &Context, channel_id: ChannelId, user_id: UserId) {
@@ -296,7 +675,7 @@ impl Handler {
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message) {
-        if msg.author.bot {
+        if msg.author.bot || self.is_shutting_down() {
             return;
         }
 
@@ -305,109 +684,181 @@ impl EventHandler for Handler {
             None => return,
         };
 
-        if !msg.mentions.iter().any(|u| u.id == bot_id) {
+        let thread = if self.config.threads.mode != ThreadMode::Off {
+            current_thread(&ctx, &msg).await
+        } else {
+            None
+        };
+        let bot_thread = thread.as_ref().is_some_and(|t| is_bot_thread(t, bot_id));
+
+        let parent_id = self
+            .parent_channel(&ctx, msg.guild_id, msg.channel_id)
+            .await;
+        let Some(content) =
+            match_trigger(&self.config.triggers, &msg, parent_id, bot_id, bot_thread)
+        else {
+            return;
+        };
+        if content.is_empty() && msg.attachments.is_empty() {
             return;
         }
 
-        let content = Self::extract_content(&msg, bot_id);
-        if content.is_empty() && msg.attachments.is_empty() {
+        let user_info = self.build_user_info(&ctx, &msg).await;
+        let role = self.role_of(msg.author.id, msg.guild_id, &user_info.roles);
+        if !role.is_admin()
+            && let Err(e) = self.limiter.admit(msg.author.id, msg.guild_id)
+        {
+            info!("Rejected request from {}: {}", msg.author.id, e);
+            let _ = msg.reply(&ctx, e.to_string()).await;
             return;
         }
 
         let _ = msg.react(&ctx, '👀').await;
         let typing = msg.channel_id.start_typing(&ctx.http);
 
-        let user_info = self.build_user_info(&ctx, &msg).await;
         let attachments = self.download_attachments(&msg).await;
-        let is_owner = msg.author.id == self.owner_id;
+        let discord_context = match thread.as_ref().filter(|_| bot_thread) {
+            Some(thread) => {
+                build_thread_context(&ctx, &msg, thread, bot_id, &self.config.threads).await
+            }
+            None => build_discord_context(&ctx, &msg, bot_id, &self.config.context).await,
+        };
+        let max_turns = if role.is_admin() {
+            self.limiter.default_max_turns()
+        } else {
+            self.limiter.max_turns_for(&user_info.roles)
+        };
 
         let input = if content.is_empty() {
-            "I've attached files. Please check them.".to_string()
+            ATTACHMENTS_ONLY_PROMPT.to_string()
         } else {
             content
         };
 
-        let (tx, mut rx) = mpsc::channel::<StreamEvent>(128);
-        let agent = self.agent.clone();
-        let channel_id_val = msg.channel_id.get();
-        let handle = tokio::spawn(async move {
-            agent
-                .process_streaming(
-                    &input,
-                    is_owner,
-                    Some(channel_id_val),
-                    Some(&user_info),
-                    &attachments,
-                    tx,
-                )
-                .await
-        });
-
         typing.stop();
 
-        let reply_msg = match msg.reply(&ctx, "…").await {
+        let new_thread = if self.config.threads.mode == ThreadMode::Always
+            && msg.guild_id.is_some()
+            && thread.is_none()
+        {
+            start_thread(&ctx, msg.channel_id, msg.id, thread_name(&input)).await
+        } else {
+            None
+        };
+        let sent = match &new_thread {
+            Some(thread) => {
+                thread
+                    .id
+                    .send_message(&ctx, CreateMessage::new().content("…"))
+                    .await
+            }
+            None => msg.reply(&ctx, "…").await,
+        };
+        let reply_msg = match sent {
             Ok(m) => m,
             Err(e) => {
                 error!("Failed to send initial reply: {}", e);
-                handle.abort();
                 return;
             }
         };
 
-        let _ = reply_msg.react(&ctx, CANCEL_EMOJI).await;
-
-        let cancelled = Arc::new(AtomicBool::new(false));
-        let mut relay = StreamRelay::new(reply_msg);
+        let request = Arc::new(ReplyRequest {
+            source_id: msg.id,
+            source_channel: new_thread.is_some().then_some(msg.channel_id),
+            input,
+            user_info,
+            attachments,
+            discord_context,
+            role,
+            guild_id: msg.guild_id,
+            channel_id: reply_msg.channel_id,
+            requester_id: msg.author.id,
+            max_turns,
+            thread_history: bot_thread,
+        });
 
-        self.active_streams.lock().await.insert(
-            relay.cancel_msg_id,
-            StreamControl {
-                abort_handle: handle.abort_handle(),
-                cancelled: cancelled.clone(),
-                requester_id: msg.author.id,
-                channel_id: msg.channel_id,
-            },
-        );
+        let relay = StreamRelay::new(reply_msg, &self.config.display, self.pacer.clone());
+        let Some((request, response, mut relay)) = self
+            .stream_reply(&ctx, request, relay, RunOptions::default())
+            .await
+        else {
+            return;
+        };
 
-        self.run_stream_loop(&ctx, &mut relay, &mut rx, msg.channel_id)
-            .await;
+        self.send_response_files_and_notify(
+            &ctx,
+            request.channel_id,
+            msg.author.id,
+            &mut relay,
+            &response,
+        )
+        .await;
+        relay.send_reasoning(&ctx).await;
+
+        if self.config.threads.mode == ThreadMode::Long
+            && msg.guild_id.is_some()
+            && thread.is_none()
+            && response.text.chars().count() > self.config.threads.min_length
+            && let Some(first) = relay.messages().first()
+        {
+            start_thread(&ctx, msg.channel_id, first.id, thread_name(&request.input)).await;
+        }
 
-        self.unregister_stream(&ctx, &relay, msg.channel_id).await;
+        self.store_session(
+            &ctx,
+            ReplySession {
+                request,
+                messages: relay.messages(),
+                response: response.text,
+                turn_id: response.turn_id,
+                model: None,
+            },
+        )
+        .await;
+    }
 
-        if cancelled.load(Ordering::Acquire) {
-            relay.finalize(&ctx, true).await;
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        let window = self.config.triggers.edit_window;
+        // Embed unfurls also arrive as updates, but without new content.
+        if window == 0 || event.content.is_none() || self.is_shutting_down() {
+            return;
+        }
+        if event.author.as_ref().is_some_and(|a| a.bot) {
             return;
         }
 
-        relay.finalize(&ctx, false).await;
+        let sent_at = match new.as_ref() {
+            Some(msg) => msg.timestamp.unix_timestamp(),
+            None => event.id.created_at().unix_timestamp(),
+        };
+        if chrono::Utc::now().timestamp() - sent_at > window as i64 {
+            return;
+        }
 
-        match handle.await {
-            Ok(Ok(response)) => {
-                self.send_response_files_and_notify(&ctx, &msg, &mut relay, response)
-                    .await;
-            }
-            Ok(Err(e)) => {
-                error!("Agent error: {}", e);
-                let _ = relay
-                    .reply_msg
-                    .edit(
-                        &ctx,
-                        EditMessage::new().content("An error occurred while processing."),
-                    )
-                    .await;
-            }
-            Err(e) if e.is_cancelled() => {}
-            Err(e) => {
-                error!("Task join error: {}", e);
-                let _ = relay
-                    .reply_msg
-                    .edit(
-                        &ctx,
-                        EditMessage::new().content("An error occurred while processing."),
-                    )
-                    .await;
+        // A reply still streaming is restarted in place by `stream_reply`.
+        {
+            let mut streams = self.active_streams.lock().await;
+            let reply_id = streams
+                .iter()
+                .find(|(_, ctrl)| ctrl.source_id == event.id)
+                .map(|(id, _)| *id);
+            if let Some(ctrl) = reply_id.and_then(|id| streams.remove(&id)) {
+                drop(streams);
+                info!("Message {} edited while streaming, restarting", event.id);
+                ctrl.superseded.store(true, Ordering::Release);
+                ctrl.cancelled.store(true, Ordering::Release);
+                ctrl.abort_handle.abort();
+                return;
             }
         }
+
+        self.rerun_edited(&ctx, event.id).await;
     }
 
     async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
@@ -424,14 +875,20 @@ impl EventHandler for Handler {
             return;
         };
 
-        let is_admin = user_id == self.owner_id;
+        let can_cancel_any = self
+            .member_role(&ctx, user_id, reaction.guild_id, reaction.member.as_ref())
+            .is_operator();
 
         let mut streams = self.active_streams.lock().await;
-        let Some(ctrl) = streams.get(&reaction.message_id) else {
-            return;
+        let requester_id = match streams.get(&reaction.message_id) {
+            Some(ctrl) => ctrl.requester_id,
+            None => match self.queue.requester(reaction.message_id) {
+                Some(requester_id) => requester_id,
+                None => return,
+            },
         };
 
-        if !is_admin && user_id != ctrl.requester_id {
+        if !can_cancel_any && user_id != requester_id {
             let emoji = reaction.emoji.clone();
             let _ = ctx
                 .http
@@ -441,6 +898,8 @@ impl EventHandler for Handler {
         }
 
         let Some(ctrl) = streams.remove(&reaction.message_id) else {
+            drop(streams);
+            self.queue.cancel(reaction.message_id);
             return;
         };
         drop(streams);
@@ -453,30 +912,80 @@ impl EventHandler for Handler {
         info!("Bot connected as {}", ready.user.name);
         *self.bot_id.write().await = Some(ready.user.id);
         self.scheduler.set_discord_http(ctx.http.clone()).await;
+        self.approvals.set_discord_http(ctx.http.clone()).await;
 
-        if let Err(e) = serenity::model::application::Command::create_global_command(
-            &ctx.http,
-            CreateCommand::new("cancelall")
-                .description("(Admin only) Cancel all active AI response streams"),
-        )
-        .await
-        {
-            error!("Failed to register /cancelall slash command: {}", e);
-        } else {
-            info!("Registered /cancelall slash command");
-        }
+        register_commands(&ctx).await;
+        self.restore_interrupted(&ctx).await;
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        let Interaction::Command(cmd) = interaction else {
+        match interaction {
+            Interaction::Command(cmd) if cmd.data.name == "cancelall" => {
+                self.handle_cancel_all(&ctx, &cmd).await;
+            }
+            Interaction::Command(cmd) if cmd.data.name == "summarize" => {
+                self.handle_summarize(&ctx, &cmd).await;
+            }
+            Interaction::Command(cmd) if cmd.data.name == "prefs" => {
+                self.handle_prefs_command(&ctx, &cmd).await;
+            }
+            Interaction::Command(cmd) if cmd.data.name == "feedback" => {
+                self.handle_feedback_command(&ctx, &cmd).await;
+            }
+            Interaction::Command(cmd) if is_menu_command(&cmd) => {
+                self.handle_message_command(&ctx, &cmd).await;
+            }
+            Interaction::Command(cmd) => {
+                self.handle_admin_command(&ctx, &cmd).await;
+            }
+            Interaction::Component(component) => {
+                self.handle_approval_button(&ctx, &component).await;
+                self.handle_reply_button(&ctx, &component).await;
+                self.handle_feedback_button(&ctx, &component).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Handler {
+    async fn handle_approval_button(&self, ctx: &Context, component: &ComponentInteraction) {
+        let Some((id, approved)) = ApprovalGate::parse_custom_id(&component.data.custom_id) else {
             return;
         };
 
-        if cmd.data.name != "cancelall" {
-            return;
+        let role = self.member_role(
+            ctx,
+            component.user.id,
+            component.guild_id,
+            component.member.as_ref(),
+        );
+        let response = if !role.is_admin() {
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("Only the owner and admins can approve tool calls.")
+                    .ephemeral(true),
+            )
+        } else if self
+            .approvals
+            .resolve(id, approved, component.user.id)
+            .await
+        {
+            CreateInteractionResponse::Acknowledge
+        } else {
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("This request has already been decided or expired.")
+                    .ephemeral(true),
+            )
+        };
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            error!("Failed to respond to approval button: {}", e);
         }
+    }
 
-        if cmd.user.id != self.owner_id {
+    async fn handle_cancel_all(&self, ctx: &Context, cmd: &CommandInteraction) {
+        if !self.command_role(ctx, cmd).is_operator() {
             let response = CreateInteractionResponse::Message(
                 CreateInteractionResponseMessage::new()
                     .content("You don't have permission to use this command.")
@@ -489,6 +998,7 @@ impl EventHandler for Handler {
         }
 
         let channel_id = cmd.channel_id;
+        let queued = self.queue.cancel_channel(channel_id);
         let mut streams = self.active_streams.lock().await;
         let keys: Vec<MessageId> = streams
             .iter()
@@ -496,7 +1006,7 @@ impl EventHandler for Handler {
             .map(|(id, _)| *id)
             .collect();
 
-        let reply = if keys.is_empty() {
+        let reply = if keys.is_empty() && queued == 0 {
             "No active streams to cancel.".to_string()
         } else {
             let count = keys.len();
@@ -507,7 +1017,10 @@ impl EventHandler for Handler {
                 ctrl.cancelled.store(true, Ordering::Release);
                 ctrl.abort_handle.abort();
             }
-            format!("Cancelled {} stream(s).", count)
+            format!(
+                "Cancelled {} stream(s) and {} queued request(s).",
+                count, queued
+            )
         };
         drop(streams);
 
@@ -0,0 +1,317 @@
+use crate::config::LimitsConfig;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Rough token count of `chars` characters, for runs whose usage is lost.
+pub(super) fn estimate_tokens(chars: usize) -> u64 {
+    chars.div_ceil(CHARS_PER_TOKEN) as u64
+}
+
+#[derive(Debug, Error)]
+pub(super) enum LimitError {
+    #[error("You're sending requests too quickly. Please try again in {0}s.")]
+    RateLimited(u64),
+    #[error("You already have {0} request(s) in progress. Please wait for them to finish.")]
+    UserBusy(usize),
+    #[error("This channel already has {0} request(s) in progress. Please try again shortly.")]
+    ChannelBusy(usize),
+    #[error("You've used up your daily token budget. It resets at midnight UTC.")]
+    UserBudgetExhausted,
+    #[error("This server has used up its daily token budget. It resets at midnight UTC.")]
+    GuildBudgetExhausted,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    recent: HashMap<UserId, VecDeque<Instant>>,
+    user_inflight: HashMap<UserId, usize>,
+    channel_inflight: HashMap<ChannelId, usize>,
+    day: Option<chrono::NaiveDate>,
+    user_tokens: HashMap<UserId, u64>,
+    guild_tokens: HashMap<GuildId, u64>,
+}
+
+impl LimiterState {
+    fn roll_day(&mut self) {
+        let today = chrono::Utc::now().date_naive();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.user_tokens.clear();
+            self.guild_tokens.clear();
+        }
+    }
+}
+
+fn lock(state: &Mutex<LimiterState>) -> MutexGuard<'_, LimiterState> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(super) struct RequestLimiter {
+    config: LimitsConfig,
+    state: Arc<Mutex<LimiterState>>,
+}
+
+/// Holds the concurrency slots of an admitted request until dropped.
+pub(super) struct LimitPermit {
+    user_id: UserId,
+    channel_id: ChannelId,
+    state: Arc<Mutex<LimiterState>>,
+}
+
+impl Drop for LimitPermit {
+    fn drop(&mut self) {
+        let mut state = lock(&self.state);
+        if let Some(count) = state.user_inflight.get_mut(&self.user_id) {
+            *count = count.saturating_sub(1);
+        }
+        if let Some(count) = state.channel_inflight.get_mut(&self.channel_id) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+impl RequestLimiter {
+    pub fn new(config: LimitsConfig) -> Self {
+        Self {
+            config,
+            state: Arc::new(Mutex::new(LimiterState::default())),
+        }
+    }
+
+    /// Admits a request without taking concurrency slots, since it may wait
+    /// in the queue first; `occupy` takes them once it runs. Also used for the
+    /// restart of a request that holds them.
+    pub fn admit(&self, user_id: UserId, guild_id: Option<GuildId>) -> Result<(), LimitError> {
+        let mut state = lock(&self.state);
+        state.roll_day();
+        self.check_budgets(&state, user_id, guild_id)?;
+        self.count_request(&mut state, user_id)
+    }
+
+    /// Takes the concurrency slots of an admitted request that starts running.
+    pub fn occupy(
+        &self,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<LimitPermit, LimitError> {
+        let mut state = lock(&self.state);
+        self.check_slots(&state, user_id, channel_id)?;
+        Ok(self.take_slots(&mut state, user_id, channel_id))
+    }
+
+    fn check_slots(
+        &self,
+        state: &LimiterState,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<(), LimitError> {
+        if let Some(max) = self.config.max_concurrent_per_user
+            && state.user_inflight.get(&user_id).copied().unwrap_or(0) >= max
+        {
+            return Err(LimitError::UserBusy(max));
+        }
+        if let Some(max) = self.config.max_concurrent_per_channel
+            && state
+                .channel_inflight
+                .get(&channel_id)
+                .copied()
+                .unwrap_or(0)
+                >= max
+        {
+            return Err(LimitError::ChannelBusy(max));
+        }
+        Ok(())
+    }
+
+    fn take_slots(
+        &self,
+        state: &mut LimiterState,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> LimitPermit {
+        *state.user_inflight.entry(user_id).or_default() += 1;
+        *state.channel_inflight.entry(channel_id).or_default() += 1;
+        LimitPermit {
+            user_id,
+            channel_id,
+            state: self.state.clone(),
+        }
+    }
+
+    fn check_budgets(
+        &self,
+        state: &LimiterState,
+        user_id: UserId,
+        guild_id: Option<GuildId>,
+    ) -> Result<(), LimitError> {
+        if let Some(budget) = self.config.daily_tokens_per_user
+            && state.user_tokens.get(&user_id).copied().unwrap_or(0) >= budget
+        {
+            return Err(LimitError::UserBudgetExhausted);
+        }
+        if let Some(budget) = self.config.daily_tokens_per_guild
+            && let Some(guild_id) = guild_id
+            && state.guild_tokens.get(&guild_id).copied().unwrap_or(0) >= budget
+        {
+            return Err(LimitError::GuildBudgetExhausted);
+        }
+        Ok(())
+    }
+
+    /// Counts a request against the per-minute rate limit.
+    fn count_request(&self, state: &mut LimiterState, user_id: UserId) -> Result<(), LimitError> {
+        let Some(rpm) = self.config.requests_per_minute else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let recent = state.recent.entry(user_id).or_default();
+        while recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW)
+        {
+            recent.pop_front();
+        }
+        if recent.len() >= rpm as usize {
+            let retry_in = recent
+                .front()
+                .map(|t| RATE_WINDOW.saturating_sub(now.duration_since(*t)))
+                .unwrap_or(RATE_WINDOW);
+            return Err(LimitError::RateLimited(retry_in.as_secs().max(1)));
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+
+    pub fn record_usage(&self, user_id: UserId, guild_id: Option<GuildId>, tokens: u64) {
+        let mut state = lock(&self.state);
+        state.roll_day();
+        *state.user_tokens.entry(user_id).or_default() += tokens;
+        if let Some(guild_id) = guild_id {
+            *state.guild_tokens.entry(guild_id).or_default() += tokens;
+        }
+    }
+
+    pub fn max_turns_for(&self, roles: &[String]) -> Option<usize> {
+        roles
+            .iter()
+            .filter_map(|role| self.config.role_max_turns.get(role).copied())
+            .max()
+            .or(self.config.max_turns)
+    }
+
+    pub fn default_max_turns(&self) -> Option<usize> {
+        self.config.max_turns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: UserId = UserId::new(1);
+    const OTHER: UserId = UserId::new(2);
+    const CHANNEL: ChannelId = ChannelId::new(10);
+    const GUILD: GuildId = GuildId::new(100);
+
+    #[test]
+    fn admit_counts_against_the_rate_limit() {
+        let limiter = RequestLimiter::new(LimitsConfig {
+            requests_per_minute: Some(2),
+            ..Default::default()
+        });
+
+        assert!(limiter.admit(USER, None).is_ok());
+        assert!(limiter.admit(USER, None).is_ok());
+        assert!(matches!(
+            limiter.admit(USER, None),
+            Err(LimitError::RateLimited(_))
+        ));
+        assert!(limiter.admit(OTHER, None).is_ok());
+    }
+
+    #[test]
+    fn admit_leaves_concurrency_slots_free() {
+        let limiter = RequestLimiter::new(LimitsConfig {
+            max_concurrent_per_user: Some(1),
+            ..Default::default()
+        });
+
+        limiter.admit(USER, None).unwrap();
+        limiter.admit(USER, None).unwrap();
+        let permit = limiter.occupy(USER, CHANNEL).unwrap();
+        assert!(matches!(
+            limiter.occupy(USER, CHANNEL),
+            Err(LimitError::UserBusy(1))
+        ));
+
+        drop(permit);
+        assert!(limiter.occupy(USER, CHANNEL).is_ok());
+    }
+
+    #[test]
+    fn occupy_limits_each_channel() {
+        let limiter = RequestLimiter::new(LimitsConfig {
+            max_concurrent_per_channel: Some(1),
+            ..Default::default()
+        });
+
+        let _permit = limiter.occupy(USER, CHANNEL).unwrap();
+        assert!(matches!(
+            limiter.occupy(OTHER, CHANNEL),
+            Err(LimitError::ChannelBusy(1))
+        ));
+        assert!(limiter.occupy(OTHER, ChannelId::new(11)).is_ok());
+    }
+
+    #[test]
+    fn spent_budgets_reject_new_requests() {
+        let limiter = RequestLimiter::new(LimitsConfig {
+            daily_tokens_per_user: Some(100),
+            daily_tokens_per_guild: Some(150),
+            ..Default::default()
+        });
+
+        limiter.record_usage(USER, Some(GUILD), 100);
+        assert!(matches!(
+            limiter.admit(USER, Some(GUILD)),
+            Err(LimitError::UserBudgetExhausted)
+        ));
+        assert!(limiter.admit(OTHER, Some(GUILD)).is_ok());
+
+        limiter.record_usage(OTHER, Some(GUILD), 50);
+        assert!(matches!(
+            limiter.admit(OTHER, Some(GUILD)),
+            Err(LimitError::GuildBudgetExhausted)
+        ));
+        assert!(limiter.admit(OTHER, None).is_ok());
+    }
+
+    #[test]
+    fn budgets_reset_on_a_new_day() {
+        let limiter = RequestLimiter::new(LimitsConfig {
+            daily_tokens_per_user: Some(100),
+            ..Default::default()
+        });
+        limiter.record_usage(USER, None, 100);
+        assert!(limiter.admit(USER, None).is_err());
+
+        lock(&limiter.state).day = chrono::Utc::now().date_naive().pred_opt();
+        assert!(limiter.admit(USER, None).is_ok());
+    }
+
+    #[test]
+    fn estimates_round_up() {
+        assert_eq!(estimate_tokens(0), 0);
+        assert_eq!(estimate_tokens(1), 1);
+        assert_eq!(estimate_tokens(8), 2);
+        assert_eq!(estimate_tokens(9), 3);
+    }
+}
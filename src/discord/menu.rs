@@ -0,0 +1,356 @@
+use super::*;
+use crate::agent::{RequestContext, StreamEvent};
+use serenity::{
+    all::CreateAttachment,
+    builder::{
+        CreateCommand, CreateInteractionResponse, CreateInteractionResponseFollowup,
+        CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse,
+    },
+    model::{
+        application::{CommandInteraction, CommandType, ResolvedTarget},
+        channel::Message,
+        id::MessageId,
+    },
+};
+use std::fmt::Write;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+const ASK: &str = "Ask RustClaw";
+const SUMMARIZE: &str = "Summarize";
+const TRANSLATE: &str = "Translate";
+
+/// Message context-menu commands ("Apps" on a message).
+pub(super) fn menu_definitions() -> Vec<CreateCommand> {
+    [ASK, SUMMARIZE, TRANSLATE]
+        .into_iter()
+        .map(|name| CreateCommand::new(name).kind(CommandType::Message))
+        .collect()
+}
+
+pub(super) fn is_menu_command(cmd: &CommandInteraction) -> bool {
+    cmd.data.kind == CommandType::Message
+}
+
+fn instruction(name: &str, locale: &str) -> Option<String> {
+    Some(match name {
+        ASK => "Someone shared the Discord message below and wants your help with it. \
+                Explain it, answer any question it asks, or diagnose the error it shows."
+            .to_string(),
+        SUMMARIZE => "Summarize the Discord message below concisely. \
+                      Keep key facts, decisions and action items."
+            .to_string(),
+        TRANSLATE => format!(
+            "Translate the Discord message below into the language of the locale `{}`. \
+             If it is already in that language, translate it into English. \
+             Reply with the translation only.",
+            locale
+        ),
+        _ => return None,
+    })
+}
+
+/// Renders a message's text, embeds and attachment names for the prompt.
+fn describe_message(msg: &Message) -> String {
+    let author = msg
+        .author
+        .global_name
+        .as_deref()
+        .unwrap_or(&msg.author.name);
+    let mut out = format!("[Message from {}]\n", author);
+    if !msg.content.trim().is_empty() {
+        let _ = writeln!(out, "{}", msg.content.trim());
+    }
+    for embed in &msg.embeds {
+        out.push_str("[Embed]\n");
+        for text in [&embed.title, &embed.url, &embed.description]
+            .into_iter()
+            .flatten()
+        {
+            let _ = writeln!(out, "{}", text);
+        }
+        for field in &embed.fields {
+            let _ = writeln!(out, "{}: {}", field.name, field.value);
+        }
+        if let Some(footer) = &embed.footer {
+            let _ = writeln!(out, "{}", footer.text);
+        }
+    }
+    if !msg.attachments.is_empty() {
+        let names: Vec<&str> = msg
+            .attachments
+            .iter()
+            .map(|a| a.filename.as_str())
+            .collect();
+        let _ = writeln!(out, "[attachments: {}]", names.join(", "));
+    }
+    out
+}
+
+impl Handler {
+    pub(super) async fn respond_ephemeral(
+        &self,
+        ctx: &Context,
+        cmd: &CommandInteraction,
+        text: &str,
+    ) {
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(text)
+                .ephemeral(true),
+        );
+        if let Err(e) = cmd.create_response(&ctx.http, response).await {
+            error!("Failed to respond to '{}': {}", cmd.data.name, e);
+        }
+    }
+
+    /// Runs a message context-menu command on its target message. The answer
+    /// is streamed as a reply to the target, or sent ephemerally when
+    /// `[context_menu].ephemeral` is set.
+    pub(super) async fn handle_message_command(&self, ctx: &Context, cmd: &CommandInteraction) {
+        let Some(instruction) = instruction(&cmd.data.name, &cmd.locale) else {
+            return;
+        };
+        let Some(ResolvedTarget::Message(target)) = cmd.data.target() else {
+            return;
+        };
+        let target = target.clone();
+
+        if self.is_shutting_down()
+            || !self
+                .channel_allowed(ctx, cmd.guild_id, cmd.channel_id)
+                .await
+        {
+            return self
+                .respond_ephemeral(ctx, cmd, "This command isn't available here right now.")
+                .await;
+        }
+
+        let role = self.command_role(ctx, cmd);
+        if !role.is_admin()
+            && let Err(e) = self.limiter.admit(cmd.user.id, cmd.guild_id)
+        {
+            return self.respond_ephemeral(ctx, cmd, &e.to_string()).await;
+        }
+
+        if let Err(e) = cmd.defer_ephemeral(&ctx.http).await {
+            error!("Failed to defer '{}': {}", cmd.data.name, e);
+            return;
+        }
+        info!(
+            "'{}' on message {} by {}",
+            cmd.data.name, target.id, cmd.user.id
+        );
+
+        let member = cmd
+            .member
+            .as_ref()
+            .map(|m| (m.nick.as_deref(), m.roles.as_slice()));
+        let mut user_info = user_info_for(ctx, &cmd.user, cmd.guild_id, member);
+        self.load_preferences(&mut user_info).await;
+        let max_turns = if role.is_admin() {
+            self.limiter.default_max_turns()
+        } else {
+            self.limiter.max_turns_for(&user_info.roles)
+        };
+        let attachments = self.download_attachments(&target).await;
+        let input = format!("{}\n\n{}", instruction, describe_message(&target));
+
+        if self.config.context_menu.ephemeral {
+            let request = ReplyRequest {
+                source_id: target.id,
+                source_channel: None,
+                input,
+                user_info,
+                attachments,
+                discord_context: String::new(),
+                role,
+                guild_id: cmd.guild_id,
+                channel_id: cmd.channel_id,
+                requester_id: cmd.user.id,
+                max_turns,
+                thread_history: false,
+            };
+            return self.answer_ephemeral(ctx, cmd, &request).await;
+        }
+
+        let reply_msg = match target
+            .channel_id
+            .send_message(
+                &ctx.http,
+                CreateMessage::new().content("…").reference_message(&target),
+            )
+            .await
+        {
+            Ok(m) => m,
+            Err(e) => {
+                error!("Failed to send initial reply: {}", e);
+                let _ = cmd
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new().content("Couldn't reply to that message."),
+                    )
+                    .await;
+                return;
+            }
+        };
+        let _ = cmd.delete_response(&ctx.http).await;
+
+        // The reply stands in for the source message: the target belongs to
+        // someone else, so edits to it must not restart the answer.
+        let request = Arc::new(ReplyRequest {
+            source_id: reply_msg.id,
+            source_channel: None,
+            input,
+            user_info,
+            attachments,
+            discord_context: String::new(),
+            role,
+            guild_id: cmd.guild_id,
+            channel_id: reply_msg.channel_id,
+            requester_id: cmd.user.id,
+            max_turns,
+            thread_history: false,
+        });
+
+        let relay = StreamRelay::new(reply_msg, &self.config.display, self.pacer.clone());
+        let Some((request, response, mut relay)) = self
+            .stream_reply(ctx, request, relay, RunOptions::default())
+            .await
+        else {
+            return;
+        };
+
+        self.send_response_files_and_notify(
+            ctx,
+            request.channel_id,
+            cmd.user.id,
+            &mut relay,
+            &response,
+        )
+        .await;
+        relay.send_reasoning(ctx).await;
+
+        self.store_session(
+            ctx,
+            ReplySession {
+                request,
+                messages: relay.messages(),
+                response: response.text,
+                turn_id: response.turn_id,
+                model: None,
+            },
+        )
+        .await;
+    }
+
+    /// Runs the request without streaming and answers in ephemeral messages.
+    async fn answer_ephemeral(
+        &self,
+        ctx: &Context,
+        cmd: &CommandInteraction,
+        request: &ReplyRequest,
+    ) {
+        let _running = self.track_stream();
+        let _slot = match self.queue.join(
+            MessageId::new(cmd.id.get()),
+            request.requester_id,
+            request.channel_id,
+            request.guild_id,
+        ) {
+            Admission::Ready(slot) => slot,
+            Admission::Queued(mut ticket) => loop {
+                match ticket.next().await {
+                    QueueUpdate::Ready(slot) if !self.is_shutting_down() => break slot,
+                    QueueUpdate::Position(_) => {}
+                    QueueUpdate::Ready(_) | QueueUpdate::Cancelled => return,
+                }
+            },
+        };
+        let _permit = match self.occupy(request) {
+            Ok(permit) => permit,
+            Err(e) => {
+                let _ = cmd
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new().content(e.to_string()),
+                    )
+                    .await;
+                return;
+            }
+        };
+
+        let (tx, mut rx) = mpsc::channel::<StreamEvent>(128);
+        let drain = tokio::spawn(async move { while rx.recv().await.is_some() {} });
+        let result = self
+            .agent
+            .process_streaming(
+                &request.input,
+                RequestContext {
+                    role: request.role,
+                    discord_channel_id: Some(request.channel_id.get()),
+                    guild_id: request.guild_id.map(|g| g.get()),
+                    user_info: Some(&request.user_info),
+                    attachments: &request.attachments,
+                    max_turns: request.max_turns,
+                    ..Default::default()
+                },
+                tx,
+            )
+            .await;
+        let _ = drain.await;
+
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Agent error: {}", e);
+                self.limiter.record_usage(
+                    request.requester_id,
+                    request.guild_id,
+                    estimate_tokens(request.input.len() + request.discord_context.len()),
+                );
+                let _ = cmd
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .content("An error occurred while processing."),
+                    )
+                    .await;
+                return;
+            }
+        };
+        self.limiter.record_usage(
+            request.requester_id,
+            request.guild_id,
+            response.total_tokens,
+        );
+
+        let mut chunks = split_message(&response.text, DISCORD_MAX_LEN).into_iter();
+        let first = chunks
+            .next()
+            .unwrap_or_else(|| "*(No response)*".to_string());
+        if let Err(e) = cmd
+            .edit_response(&ctx.http, EditInteractionResponse::new().content(first))
+            .await
+        {
+            error!("Failed to respond to '{}': {}", cmd.data.name, e);
+        }
+        for chunk in chunks {
+            let followup = CreateInteractionResponseFollowup::new()
+                .content(chunk)
+                .ephemeral(true);
+            let _ = cmd.create_followup(&ctx.http, followup).await;
+        }
+        for file in &response.files {
+            let Ok(attachment) = CreateAttachment::path(&file.path).await else {
+                continue;
+            };
+            let followup = CreateInteractionResponseFollowup::new()
+                .add_file(attachment)
+                .ephemeral(true);
+            if let Err(e) = cmd.create_followup(&ctx.http, followup).await {
+                error!("Failed to send file '{}': {}", file.filename, e);
+            }
+        }
+    }
+}
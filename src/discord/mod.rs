@@ -1,16 +1,56 @@
 use anyhow::Result;
 use serenity::{model::id::UserId, prelude::*};
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize},
+    },
+    time::Duration,
+};
 use tokio::sync::Mutex;
 
+mod commands;
+mod context;
+mod feedback;
 mod handler;
+mod limits;
+mod menu;
+mod overflow;
+mod pacer;
+mod prefs;
+mod queue;
 mod relay;
+mod session;
+mod shutdown;
+mod summary;
+mod thread;
+mod trigger;
 mod util;
 
-use crate::{agent::Agent, config::Config, scheduler::Scheduler};
+use crate::{
+    agent::Agent, approval::ApprovalGate, channels::ThreadParents, config::Config,
+    permissions::Role, personas::PersonaStore, scheduler::Scheduler, vector_db::VectorDb,
+};
+use commands::*;
+use context::*;
+use feedback::*;
 pub(crate) use handler::send_agent_response;
 use handler::*;
+use limits::*;
+use menu::*;
+use overflow::*;
+use pacer::*;
+use prefs::*;
+use queue::*;
 use relay::*;
+use session::*;
+pub use shutdown::ShutdownHandle;
+use shutdown::*;
+pub(crate) use summary::post_channel_summary;
+use summary::*;
+use thread::*;
+use trigger::*;
 use util::*;
 
 pub const DISCORD_MAX_LEN: usize = 2000;
@@ -23,8 +63,7 @@ pub const NOTIFY_DELETE_DELAY: Duration = Duration::from_secs(2);
 
 pub struct Bot {
     config: Config,
-    agent: Arc<dyn Agent>,
-    scheduler: Arc<Scheduler>,
+    handler: Arc<Handler>,
 }
 
 impl Bot {
@@ -32,33 +71,51 @@ impl Bot {
         config: Config,
         agent: Arc<dyn Agent>,
         scheduler: Arc<Scheduler>,
+        approvals: Arc<ApprovalGate>,
+        vector_db: Arc<VectorDb>,
+        personas: Arc<PersonaStore>,
+        thread_parents: Arc<ThreadParents>,
     ) -> Result<Self> {
-        Ok(Self {
-            config,
+        let handler = Arc::new(Handler {
             agent,
+            config: config.clone(),
+            bot_id: Arc::new(RwLock::new(None)),
             scheduler,
-        })
+            approvals,
+            vector_db,
+            personas,
+            thread_parents,
+            started: std::time::Instant::now(),
+            http_client: reqwest::Client::new(),
+            active_streams: Arc::new(Mutex::new(HashMap::new())),
+            pacer: Arc::new(EditPacer::default()),
+            limiter: RequestLimiter::new(config.limits.clone()),
+            queue: RequestQueue::new(config.queue.scope),
+            sessions: Mutex::new(ReplySessions::default()),
+            shutting_down: AtomicBool::new(false),
+            running_streams: AtomicUsize::new(0),
+            interrupted: Mutex::new(Vec::new()),
+        });
+
+        Ok(Self { config, handler })
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            handler: self.handler.clone(),
+        }
     }
 
     pub async fn start(self) -> Result<()> {
-        let intents = GatewayIntents::GUILD_MESSAGES
+        let intents = GatewayIntents::GUILDS
+            | GatewayIntents::GUILD_MESSAGES
             | GatewayIntents::DIRECT_MESSAGES
             | GatewayIntents::MESSAGE_CONTENT
             | GatewayIntents::GUILD_MESSAGE_REACTIONS
             | GatewayIntents::DIRECT_MESSAGE_REACTIONS;
 
-        let handler = Handler {
-            agent: self.agent,
-            config: self.config.clone(),
-            bot_id: Arc::new(RwLock::new(None)),
-            owner_id: UserId::new(self.config.discord.owner_id),
-            scheduler: self.scheduler,
-            http_client: reqwest::Client::new(),
-            active_streams: Arc::new(Mutex::new(HashMap::new())),
-        };
-
         let mut client = Client::builder(&self.config.discord.token, intents)
-            .event_handler(handler)
+            .event_handler_arc(self.handler)
             .await?;
 
         client.start().await?;
@@ -0,0 +1,244 @@
+use super::*;
+use crate::config::{DisplayConfig, OverflowMode};
+use serenity::builder::{CreateAttachment, CreateEmbed, CreateMessage, EditMessage};
+use std::fmt::Write;
+
+/// Longest embed description Discord accepts.
+const EMBED_MAX_LEN: usize = 4096;
+const SUMMARY_MAX_CHARS: usize = 300;
+/// Share of characters inside code blocks above which a response counts as code.
+const CODE_RATIO: f32 = 0.5;
+
+/// One message of a long response.
+pub(super) struct LongPart {
+    content: String,
+    embed: Option<CreateEmbed>,
+    file: Option<CreateAttachment>,
+}
+
+impl LongPart {
+    pub fn edit(&self) -> EditMessage {
+        let mut edit = EditMessage::new()
+            .content(&self.content)
+            .embeds(self.embed.iter().cloned().collect());
+        if let Some(file) = &self.file {
+            edit = edit.new_attachment(file.clone());
+        }
+        edit
+    }
+
+    pub fn create(&self) -> CreateMessage {
+        let mut message = CreateMessage::new()
+            .content(&self.content)
+            .embeds(self.embed.iter().cloned().collect());
+        if let Some(file) = &self.file {
+            message = message.add_file(file.clone());
+        }
+        message
+    }
+}
+
+/// Whether a finished response is too long to post as plain messages.
+pub(super) fn needs_long_response(text: &str, display: &DisplayConfig) -> bool {
+    display.overflow != OverflowMode::Messages
+        && split_message(text, DISCORD_MAX_LEN).len() > display.overflow_after.max(1)
+}
+
+/// Renders a long response as embeds or as one file, following `mode`.
+/// `auto` picks a file for code-heavy text and for prose that would still
+/// need more than `max_messages` embeds. `note` is shown after the response.
+pub(super) fn long_response_parts(
+    text: &str,
+    note: Option<&str>,
+    mode: OverflowMode,
+    max_messages: usize,
+) -> Vec<LongPart> {
+    let embeds = split_message(text, EMBED_MAX_LEN);
+    let use_embeds = match mode {
+        OverflowMode::Embed => true,
+        OverflowMode::Attachment => false,
+        OverflowMode::Auto | OverflowMode::Messages => {
+            code_ratio(text) < CODE_RATIO && embeds.len() <= max_messages.max(1)
+        }
+    };
+    if !use_embeds {
+        return vec![attachment_part(text, note)];
+    }
+
+    let count = embeds.len();
+    embeds
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| LongPart {
+            content: match note {
+                Some(note) if i + 1 == count => note.to_string(),
+                _ => String::new(),
+            },
+            embed: Some(CreateEmbed::new().description(chunk)),
+            file: None,
+        })
+        .collect()
+}
+
+fn code_ratio(text: &str) -> f32 {
+    let mut in_code = false;
+    let mut code_chars = 0;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        } else if in_code {
+            code_chars += line.chars().count();
+        }
+    }
+    code_chars as f32 / text.chars().count().max(1) as f32
+}
+
+/// A response that is a single code block, as `(language, code)`.
+fn single_code_block(text: &str) -> Option<(&str, &str)> {
+    let body = text.trim().strip_prefix("```")?.strip_suffix("```")?;
+    let (lang, code) = body.split_once('\n')?;
+    (!code.contains("```")).then_some((lang.trim(), code))
+}
+
+fn extension(lang: &str) -> &'static str {
+    match lang.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => "rs",
+        "python" | "py" => "py",
+        "javascript" | "js" => "js",
+        "typescript" | "ts" => "ts",
+        "bash" | "sh" | "shell" | "zsh" => "sh",
+        "c++" | "cpp" => "cpp",
+        "yaml" | "yml" => "yml",
+        "markdown" | "md" => "md",
+        "json" => "json",
+        "toml" => "toml",
+        "html" => "html",
+        "css" => "css",
+        "c" => "c",
+        "go" => "go",
+        "java" => "java",
+        "sql" => "sql",
+        _ => "txt",
+    }
+}
+
+/// The first paragraph of prose, shortened.
+fn summary(text: &str) -> String {
+    let paragraph = text
+        .split("\n\n")
+        .map(str::trim)
+        .find(|p| !p.is_empty() && !p.starts_with("```"))
+        .unwrap_or_default();
+    let paragraph = paragraph.split("```").next().unwrap_or_default().trim();
+    if paragraph.chars().count() > SUMMARY_MAX_CHARS {
+        let truncated: String = paragraph.chars().take(SUMMARY_MAX_CHARS).collect();
+        format!("{}…", truncated.trim_end())
+    } else {
+        paragraph.to_string()
+    }
+}
+
+fn attachment_part(text: &str, note: Option<&str>) -> LongPart {
+    let (filename, body) = match single_code_block(text) {
+        Some((lang, code)) => (format!("response.{}", extension(lang)), code),
+        None => ("response.md".to_string(), text),
+    };
+
+    let mut content = summary(text);
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    let _ = write!(
+        content,
+        "-# 📎 Full response in `{}` ({} lines, {} characters)",
+        filename,
+        body.lines().count(),
+        body.chars().count()
+    );
+    if let Some(note) = note {
+        content.push_str("\n\n");
+        content.push_str(note);
+    }
+
+    LongPart {
+        content,
+        embed: None,
+        file: Some(CreateAttachment::bytes(body.as_bytes().to_vec(), filename)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prose that takes `n` full Discord messages.
+    fn prose(messages: usize) -> String {
+        "word ".repeat(messages * DISCORD_MAX_LEN / 5)
+    }
+
+    fn display(overflow: OverflowMode, overflow_after: usize) -> DisplayConfig {
+        DisplayConfig {
+            overflow,
+            overflow_after,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn messages_mode_never_needs_a_long_response() {
+        assert!(!needs_long_response(&prose(10), &DisplayConfig::default()));
+        assert!(!needs_long_response(
+            &prose(10),
+            &display(OverflowMode::Messages, 1)
+        ));
+    }
+
+    #[test]
+    fn long_response_starts_past_overflow_after() {
+        let display = display(OverflowMode::Auto, 2);
+        assert!(!needs_long_response(&prose(1), &display));
+        assert!(needs_long_response(&prose(3), &display));
+    }
+
+    #[test]
+    fn embed_mode_splits_into_embeds_with_the_note_last() {
+        let text = prose(3);
+        let parts = long_response_parts(&text, Some("*(Cancelled)*"), OverflowMode::Embed, 1);
+
+        assert_eq!(parts.len(), split_message(&text, EMBED_MAX_LEN).len());
+        assert!(parts.iter().all(|p| p.embed.is_some() && p.file.is_none()));
+        assert_eq!(parts.last().unwrap().content, "*(Cancelled)*");
+        assert!(
+            parts[..parts.len() - 1]
+                .iter()
+                .all(|p| p.content.is_empty())
+        );
+    }
+
+    #[test]
+    fn attachment_mode_sends_one_file() {
+        let text = format!("Intro paragraph.\n\n{}", prose(3));
+        let parts = long_response_parts(&text, None, OverflowMode::Attachment, 5);
+
+        assert_eq!(parts.len(), 1);
+        let file = parts[0].file.as_ref().unwrap();
+        assert_eq!(file.filename, "response.md");
+        assert_eq!(file.data, text.as_bytes());
+        assert!(parts[0].content.starts_with("Intro paragraph.\n-# 📎"));
+    }
+
+    #[test]
+    fn auto_mode_files_code_and_very_long_prose() {
+        let code = format!("```rust\n{}\n```", "let x = 1;\n".repeat(500));
+        let parts = long_response_parts(&code, None, OverflowMode::Auto, 5);
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].file.as_ref().unwrap().filename, "response.rs");
+
+        let parts = long_response_parts(&prose(3), None, OverflowMode::Auto, 5);
+        assert!(parts.iter().all(|p| p.embed.is_some()));
+
+        let parts = long_response_parts(&prose(30), None, OverflowMode::Auto, 2);
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].file.is_some());
+    }
+}
@@ -0,0 +1,202 @@
+use super::*;
+use serenity::{
+    builder::EditMessage,
+    http::HttpError,
+    model::{channel::Message, id::ChannelId},
+};
+use std::{
+    sync::{Mutex as SyncMutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+use tracing::{error, warn};
+
+/// Gap between edits in a channel while several replies stream into it.
+const SHARED_EDIT_INTERVAL: Duration = Duration::from_millis(1200);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Attempts for an edit that must land.
+const MAX_ATTEMPTS: usize = 6;
+
+#[derive(Default)]
+struct ChannelPace {
+    streams: usize,
+    next_edit: Option<Instant>,
+    backoff: Duration,
+}
+
+impl ChannelPace {
+    fn gap(&self) -> Duration {
+        let base = if self.streams > 1 {
+            SHARED_EDIT_INTERVAL
+        } else {
+            EDIT_INTERVAL
+        };
+        base + self.backoff
+    }
+}
+
+/// What became of an edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EditStatus {
+    Done,
+    /// Skipped until the channel's slot is free, or failed in a way worth
+    /// retrying (rate limit, server error).
+    Deferred,
+    /// Failed for good, e.g. the message was deleted or is no longer
+    /// accessible; editing it again won't help.
+    Failed,
+}
+
+/// Spaces message edits per channel across all streaming replies, so several
+/// answers in one channel share its rate-limit bucket instead of racing for it.
+#[derive(Default)]
+pub(super) struct EditPacer {
+    channels: SyncMutex<HashMap<ChannelId, ChannelPace>>,
+}
+
+/// Counts a streaming reply in its channel until dropped.
+pub(super) struct PaceGuard<'a> {
+    pacer: &'a EditPacer,
+    channel_id: ChannelId,
+}
+
+impl Drop for PaceGuard<'_> {
+    fn drop(&mut self) {
+        let mut channels = self.pacer.lock();
+        if let Some(pace) = channels.get_mut(&self.channel_id) {
+            pace.streams = pace.streams.saturating_sub(1);
+            if pace.streams == 0 {
+                channels.remove(&self.channel_id);
+            }
+        }
+    }
+}
+
+/// Rate limits and server errors are worth retrying; anything else (e.g. a
+/// deleted message) is not.
+fn is_retryable(e: &serenity::Error) -> bool {
+    match e {
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) => {
+            let status = response.status_code.as_u16();
+            status == 429 || status >= 500
+        }
+        serenity::Error::Http(HttpError::Request(_)) => true,
+        _ => false,
+    }
+}
+
+fn is_rate_limited(e: &serenity::Error) -> bool {
+    matches!(
+        e,
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response))
+            if response.status_code.as_u16() == 429
+    )
+}
+
+impl EditPacer {
+    fn lock(&self) -> MutexGuard<'_, HashMap<ChannelId, ChannelPace>> {
+        self.channels.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn join(&self, channel_id: ChannelId) -> PaceGuard<'_> {
+        self.lock().entry(channel_id).or_default().streams += 1;
+        PaceGuard {
+            pacer: self,
+            channel_id,
+        }
+    }
+
+    /// How long until the channel's next edit slot is free.
+    pub fn ready_in(&self, channel_id: ChannelId) -> Duration {
+        self.lock()
+            .get(&channel_id)
+            .and_then(|pace| pace.next_edit)
+            .map_or(Duration::ZERO, |next| {
+                next.saturating_duration_since(Instant::now())
+            })
+    }
+
+    /// Takes the channel's next edit slot, or returns how long until it is free.
+    fn claim(&self, channel_id: ChannelId) -> Result<(), Duration> {
+        let mut channels = self.lock();
+        let pace = channels.entry(channel_id).or_default();
+        let now = Instant::now();
+        match pace.next_edit {
+            Some(next) if next > now => Err(next - now),
+            _ => {
+                pace.next_edit = Some(now + pace.gap());
+                Ok(())
+            }
+        }
+    }
+
+    fn record(&self, channel_id: ChannelId, result: &serenity::Result<()>) {
+        let mut channels = self.lock();
+        let pace = channels.entry(channel_id).or_default();
+        match result {
+            Ok(()) => pace.backoff = Duration::ZERO,
+            Err(e) if is_retryable(e) => {
+                pace.backoff = (pace.backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+                pace.next_edit = Some(Instant::now() + pace.backoff);
+                if is_rate_limited(e) {
+                    warn!(
+                        "Rate limited editing in channel {}, backing off {:?}",
+                        channel_id, pace.backoff
+                    );
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// An in-progress edit: sent only if the channel's slot is free. When it
+    /// is deferred, the caller keeps the content and tries again on a later
+    /// tick, so skipped deltas coalesce into one edit.
+    pub async fn try_edit(
+        &self,
+        ctx: &Context,
+        msg: &mut Message,
+        builder: EditMessage,
+    ) -> EditStatus {
+        if self.claim(msg.channel_id).is_err() {
+            return EditStatus::Deferred;
+        }
+        let result = msg.edit(ctx, builder).await;
+        self.record(msg.channel_id, &result);
+        match result {
+            Ok(()) => EditStatus::Done,
+            Err(e) if is_retryable(&e) => EditStatus::Deferred,
+            Err(e) => {
+                error!("Failed to edit message {}: {}", msg.id, e);
+                EditStatus::Failed
+            }
+        }
+    }
+
+    /// An edit that must land, like the final content of a reply: waits for
+    /// the channel's slot and retries rate-limited or failed requests.
+    pub async fn edit(&self, ctx: &Context, msg: &mut Message, builder: EditMessage) -> EditStatus {
+        for attempt in 1..=MAX_ATTEMPTS {
+            while let Err(wait) = self.claim(msg.channel_id) {
+                tokio::time::sleep(wait).await;
+            }
+            let result = msg.edit(ctx, builder.clone()).await;
+            self.record(msg.channel_id, &result);
+            match result {
+                Ok(()) => return EditStatus::Done,
+                Err(e) if is_retryable(&e) && attempt < MAX_ATTEMPTS => {
+                    warn!("Edit of message {} failed, retrying: {}", msg.id, e);
+                }
+                Err(e) if is_retryable(&e) => {
+                    error!("Failed to edit message {}: {}", msg.id, e);
+                    return EditStatus::Deferred;
+                }
+                Err(e) => {
+                    error!("Failed to edit message {}: {}", msg.id, e);
+                    return EditStatus::Failed;
+                }
+            }
+        }
+        EditStatus::Deferred
+    }
+}
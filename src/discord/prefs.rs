@@ -0,0 +1,92 @@
+use super::*;
+use crate::agent::{Preference, UserPreferences};
+use serenity::{
+    builder::{CreateCommand, CreateCommandOption},
+    model::application::{CommandInteraction, CommandOptionType},
+};
+use std::fmt::Write;
+use tracing::error;
+
+pub(super) fn prefs_command() -> CreateCommand {
+    let preference = |required: bool| {
+        Preference::ALL.into_iter().fold(
+            CreateCommandOption::new(CommandOptionType::String, "preference", "Which preference")
+                .required(required),
+            |option, p| option.add_string_choice(p.name(), p.name()),
+        )
+    };
+    CreateCommand::new("prefs")
+        .description("Your answer preferences")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show your preferences",
+        ))
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Set a preference")
+                .add_sub_option(preference(true))
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "value",
+                        "e.g. German, brief, Europe/Berlin, metric",
+                    )
+                    .required(true),
+                ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "clear",
+                "Clear one preference, or all of them",
+            )
+            .add_sub_option(preference(false)),
+        )
+}
+
+fn describe(preferences: &UserPreferences) -> String {
+    if preferences.is_empty() {
+        return "No preferences set. Use `/prefs set` to add one.".to_string();
+    }
+    let mut out = String::new();
+    for preference in Preference::ALL {
+        if let Some(value) = preferences.get(preference) {
+            let _ = writeln!(out, "**{}**: {}", preference.name(), value);
+        }
+    }
+    out
+}
+
+impl Handler {
+    pub(super) async fn handle_prefs_command(&self, ctx: &Context, cmd: &CommandInteraction) {
+        let reply = match self.run_prefs_command(cmd).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                error!("/prefs failed for {}: {}", cmd.user.id, e);
+                format!("Couldn't update your preferences: {}", e)
+            }
+        };
+        self.respond_ephemeral(ctx, cmd, &reply).await;
+    }
+
+    async fn run_prefs_command(&self, cmd: &CommandInteraction) -> anyhow::Result<String> {
+        let (sub, options) = subcommand(cmd).unwrap_or_default();
+        let user_id = cmd.user.id.get();
+        let mut preferences = self.vector_db.user_preferences(user_id).await?;
+        let preference = string_option(&options, "preference").and_then(Preference::parse);
+
+        match (sub, preference) {
+            ("set", Some(preference)) => {
+                let value = string_option(&options, "value").unwrap_or_default();
+                preferences.set(preference, value)?;
+            }
+            ("clear", Some(preference)) => preferences.set(preference, "")?,
+            ("clear", None) => preferences = UserPreferences::default(),
+            _ => return Ok(describe(&preferences)),
+        }
+        self.vector_db
+            .save_user_preferences(user_id, preferences.clone())
+            .await?;
+        Ok(describe(&preferences))
+    }
+}
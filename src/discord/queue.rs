@@ -0,0 +1,365 @@
+use crate::config::QueueScope;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::sync::watch;
+
+type Lanes = HashMap<u64, Lane>;
+
+/// One serialized line of work. `running` stays set while the slot is handed
+/// from one request to the next.
+#[derive(Default)]
+struct Lane {
+    running: bool,
+    waiting: VecDeque<Waiter>,
+}
+
+struct Waiter {
+    reply_id: MessageId,
+    requester_id: UserId,
+    channel_id: ChannelId,
+    /// 1-based queue position; 0 hands over the slot.
+    position: watch::Sender<usize>,
+}
+
+impl Lane {
+    fn renumber(&self) {
+        for (i, waiter) in self.waiting.iter().enumerate() {
+            waiter.position.send_if_modified(|position| {
+                let changed = *position != i + 1;
+                *position = i + 1;
+                changed
+            });
+        }
+    }
+}
+
+fn lock(lanes: &Mutex<Lanes>) -> MutexGuard<'_, Lanes> {
+    lanes.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Hands the lane to the next waiter that is still listening, or frees it.
+fn advance(lanes: &mut Lanes, key: u64) {
+    let Some(lane) = lanes.get_mut(&key) else {
+        return;
+    };
+    while let Some(waiter) = lane.waiting.pop_front() {
+        if waiter.position.send(0).is_ok() {
+            lane.renumber();
+            return;
+        }
+    }
+    lanes.remove(&key);
+}
+
+/// Serializes requests sharing a channel, user or guild so that they don't
+/// interleave in the sandbox and in recent memory.
+pub(super) struct RequestQueue {
+    scope: QueueScope,
+    lanes: Arc<Mutex<Lanes>>,
+}
+
+/// The right to run; the next queued request starts when it is dropped.
+pub(super) struct QueueSlot {
+    key: Option<u64>,
+    lanes: Arc<Mutex<Lanes>>,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            advance(&mut lock(&self.lanes), key);
+        }
+    }
+}
+
+pub(super) struct QueueTicket {
+    key: u64,
+    reply_id: MessageId,
+    position: watch::Receiver<usize>,
+    lanes: Arc<Mutex<Lanes>>,
+    done: bool,
+}
+
+pub(super) enum Admission {
+    Ready(QueueSlot),
+    Queued(QueueTicket),
+}
+
+pub(super) enum QueueUpdate {
+    Position(usize),
+    Ready(QueueSlot),
+    Cancelled,
+}
+
+impl QueueTicket {
+    pub fn position(&self) -> usize {
+        *self.position.borrow()
+    }
+
+    /// Waits for the next change: a new position, the turn, or cancellation.
+    pub async fn next(&mut self) -> QueueUpdate {
+        if self.position.changed().await.is_err() {
+            self.done = true;
+            return QueueUpdate::Cancelled;
+        }
+        match *self.position.borrow_and_update() {
+            0 => {
+                self.done = true;
+                QueueUpdate::Ready(QueueSlot {
+                    key: Some(self.key),
+                    lanes: self.lanes.clone(),
+                })
+            }
+            position => QueueUpdate::Position(position),
+        }
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut lanes = lock(&self.lanes);
+        if let Some(lane) = lanes.get_mut(&self.key)
+            && let Some(i) = lane
+                .waiting
+                .iter()
+                .position(|w| w.reply_id == self.reply_id)
+        {
+            lane.waiting.remove(i);
+            lane.renumber();
+            return;
+        }
+        // The turn was handed over but never taken.
+        if *self.position.borrow() == 0 {
+            advance(&mut lanes, self.key);
+        }
+    }
+}
+
+impl RequestQueue {
+    pub fn new(scope: QueueScope) -> Self {
+        Self {
+            scope,
+            lanes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn key(
+        &self,
+        requester_id: UserId,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> Option<u64> {
+        match self.scope {
+            QueueScope::Channel => Some(channel_id.get()),
+            QueueScope::User => Some(requester_id.get()),
+            QueueScope::Guild => Some(guild_id.map_or(channel_id.get(), |g| g.get())),
+            QueueScope::Off => None,
+        }
+    }
+
+    /// Takes the slot if it is free, or joins the end of the queue.
+    /// `reply_id` is the placeholder message used to cancel the request.
+    pub fn join(
+        &self,
+        reply_id: MessageId,
+        requester_id: UserId,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> Admission {
+        let Some(key) = self.key(requester_id, channel_id, guild_id) else {
+            return Admission::Ready(QueueSlot {
+                key: None,
+                lanes: self.lanes.clone(),
+            });
+        };
+
+        let mut lanes = lock(&self.lanes);
+        let lane = lanes.entry(key).or_default();
+        if !lane.running {
+            lane.running = true;
+            return Admission::Ready(QueueSlot {
+                key: Some(key),
+                lanes: self.lanes.clone(),
+            });
+        }
+
+        let (tx, rx) = watch::channel(lane.waiting.len() + 1);
+        lane.waiting.push_back(Waiter {
+            reply_id,
+            requester_id,
+            channel_id,
+            position: tx,
+        });
+        Admission::Queued(QueueTicket {
+            key,
+            reply_id,
+            position: rx,
+            lanes: self.lanes.clone(),
+            done: false,
+        })
+    }
+
+    /// The requester of a queued (not yet started) request.
+    pub fn requester(&self, reply_id: MessageId) -> Option<UserId> {
+        lock(&self.lanes)
+            .values()
+            .flat_map(|lane| &lane.waiting)
+            .find(|w| w.reply_id == reply_id)
+            .map(|w| w.requester_id)
+    }
+
+    /// Removes queued requests matching `filter`. Returns how many were removed.
+    fn remove_where(&self, filter: impl Fn(&Waiter) -> bool) -> usize {
+        let mut lanes = lock(&self.lanes);
+        let mut removed = 0;
+        for lane in lanes.values_mut() {
+            let before = lane.waiting.len();
+            lane.waiting.retain(|w| !filter(w));
+            if lane.waiting.len() != before {
+                removed += before - lane.waiting.len();
+                lane.renumber();
+            }
+        }
+        removed
+    }
+
+    pub fn cancel(&self, reply_id: MessageId) -> bool {
+        self.remove_where(|w| w.reply_id == reply_id) > 0
+    }
+
+    pub fn cancel_channel(&self, channel_id: ChannelId) -> usize {
+        self.remove_where(|w| w.channel_id == channel_id)
+    }
+
+    pub fn cancel_all(&self) -> usize {
+        self.remove_where(|_| true)
+    }
+
+    pub fn queued(&self) -> usize {
+        lock(&self.lanes)
+            .values()
+            .map(|lane| lane.waiting.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNEL: ChannelId = ChannelId::new(10);
+
+    fn join(queue: &RequestQueue, reply: u64, user: u64, channel: ChannelId) -> Admission {
+        queue.join(MessageId::new(reply), UserId::new(user), channel, None)
+    }
+
+    fn ready(admission: Admission) -> QueueSlot {
+        match admission {
+            Admission::Ready(slot) => slot,
+            Admission::Queued(_) => panic!("expected the slot"),
+        }
+    }
+
+    fn queued(admission: Admission) -> QueueTicket {
+        match admission {
+            Admission::Ready(_) => panic!("expected a queue ticket"),
+            Admission::Queued(ticket) => ticket,
+        }
+    }
+
+    #[tokio::test]
+    async fn hands_the_slot_to_the_next_in_line() {
+        let queue = RequestQueue::new(QueueScope::Channel);
+        let first = ready(join(&queue, 1, 1, CHANNEL));
+        let mut second = queued(join(&queue, 2, 2, CHANNEL));
+        let mut third = queued(join(&queue, 3, 3, CHANNEL));
+        assert_eq!((second.position(), third.position()), (1, 2));
+
+        drop(first);
+        let turn = second.next().await;
+        assert!(matches!(turn, QueueUpdate::Ready(_)));
+        assert!(matches!(third.next().await, QueueUpdate::Position(1)));
+        assert_eq!(queue.queued(), 1);
+    }
+
+    #[tokio::test]
+    async fn lanes_are_independent() {
+        let queue = RequestQueue::new(QueueScope::Channel);
+        let _first = ready(join(&queue, 1, 1, CHANNEL));
+        let _other = ready(join(&queue, 2, 1, ChannelId::new(11)));
+
+        let queue = RequestQueue::new(QueueScope::User);
+        let _first = ready(join(&queue, 1, 1, CHANNEL));
+        queued(join(&queue, 2, 1, ChannelId::new(11)));
+    }
+
+    #[tokio::test]
+    async fn off_never_queues() {
+        let queue = RequestQueue::new(QueueScope::Off);
+        let _first = ready(join(&queue, 1, 1, CHANNEL));
+        let _second = ready(join(&queue, 2, 1, CHANNEL));
+    }
+
+    #[tokio::test]
+    async fn cancelled_tickets_are_told_and_skipped() {
+        let queue = RequestQueue::new(QueueScope::Channel);
+        let first = ready(join(&queue, 1, 1, CHANNEL));
+        let mut second = queued(join(&queue, 2, 2, CHANNEL));
+        let mut third = queued(join(&queue, 3, 3, CHANNEL));
+
+        assert_eq!(queue.requester(MessageId::new(2)), Some(UserId::new(2)));
+        assert!(queue.cancel(MessageId::new(2)));
+        assert!(matches!(second.next().await, QueueUpdate::Cancelled));
+        assert!(matches!(third.next().await, QueueUpdate::Position(1)));
+
+        drop(first);
+        assert!(matches!(third.next().await, QueueUpdate::Ready(_)));
+    }
+
+    #[tokio::test]
+    async fn cancel_all_empties_every_lane() {
+        let queue = RequestQueue::new(QueueScope::Channel);
+        let _first = ready(join(&queue, 1, 1, CHANNEL));
+        let _other = ready(join(&queue, 2, 1, ChannelId::new(11)));
+        let mut waiting = queued(join(&queue, 3, 1, CHANNEL));
+        let _waiting = queued(join(&queue, 4, 1, ChannelId::new(11)));
+
+        assert_eq!(queue.cancel_all(), 2);
+        assert_eq!(queue.queued(), 0);
+        assert!(matches!(waiting.next().await, QueueUpdate::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn dropped_tickets_leave_the_queue() {
+        let queue = RequestQueue::new(QueueScope::Channel);
+        let first = ready(join(&queue, 1, 1, CHANNEL));
+        let second = queued(join(&queue, 2, 2, CHANNEL));
+        let mut third = queued(join(&queue, 3, 3, CHANNEL));
+
+        drop(second);
+        assert!(matches!(third.next().await, QueueUpdate::Position(1)));
+        drop(first);
+        assert!(matches!(third.next().await, QueueUpdate::Ready(_)));
+    }
+
+    #[tokio::test]
+    async fn a_turn_that_is_never_taken_passes_on() {
+        let queue = RequestQueue::new(QueueScope::Channel);
+        let first = ready(join(&queue, 1, 1, CHANNEL));
+        let second = queued(join(&queue, 2, 2, CHANNEL));
+        let mut third = queued(join(&queue, 3, 3, CHANNEL));
+
+        drop(first);
+        assert_eq!(second.position(), 0);
+        drop(second);
+        let turn = third.next().await;
+        assert!(matches!(turn, QueueUpdate::Ready(_)));
+        queued(join(&queue, 4, 4, CHANNEL));
+    }
+}
@@ -1,51 +1,367 @@
 use super::*;
+use crate::config::{DisplayConfig, OverflowMode, ReasoningMode};
 use serenity::{
-    builder::EditMessage,
+    builder::{CreateThread, EditMessage},
     model::{
         channel::Message,
         id::{ChannelId, MessageId},
     },
 };
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Write,
+    time::{Duration, Instant},
+};
 use tokio::sync::Mutex;
-use tracing::error;
+use tracing::{error, warn};
+
+const MAX_TOOL_STATUS_LINES: usize = 5;
+const REASONING_HEADER: &str = "-# 💭 Reasoning\n";
+const THINKING_PLACEHOLDER: &str = "-# 💭 Thinking…";
+const TOOL_STATUS_PREFIX: &str = "-# 🔧";
+const LONG_RESPONSE_STATUS: &str = "-# 📄 Long response";
+
+struct ToolStatus {
+    id: String,
+    name: String,
+    summary: String,
+    started: Instant,
+    outcome: Option<(bool, Duration)>,
+}
+
+impl ToolStatus {
+    fn format_line(&self, out: &mut String) {
+        let _ = write!(out, "{} {}", TOOL_STATUS_PREFIX, self.name);
+        if !self.summary.is_empty() {
+            let _ = write!(out, ": `{}`", self.summary);
+        }
+        match self.outcome {
+            Some((true, elapsed)) => {
+                let _ = write!(out, " ✓ {:.1}s", elapsed.as_secs_f32());
+            }
+            Some((false, elapsed)) => {
+                let _ = write!(out, " ✗ {:.1}s", elapsed.as_secs_f32());
+            }
+            None => out.push_str(" …"),
+        }
+    }
+}
 
 pub(super) struct StreamRelay {
     pub reply_msg: Message,
     overflow: Vec<Message>,
+    spare: VecDeque<Message>,
     pub accumulated: String,
     pub last_edit: Instant,
     pub cancel_msg_id: MessageId,
+    tools: Vec<ToolStatus>,
+    answer_started: bool,
+    hide_tool_status: bool,
+    collapse_tool_status: bool,
+    reasoning: String,
+    reasoning_mode: ReasoningMode,
+    /// The whole answer streamed into this relay.
+    transcript: String,
+    overflow_mode: OverflowMode,
+    overflow_after: usize,
+    /// Past `overflow_after` messages: the rest is only shown as a preview
+    /// until `finalize` delivers the answer as embeds or a file.
+    collapsed: bool,
+    pacer: Arc<EditPacer>,
+    /// Content changed since the last edit that went through.
+    dirty: bool,
+    /// Messages whose edits failed for good (e.g. deleted); not edited again.
+    lost: HashSet<MessageId>,
 }
 
 impl StreamRelay {
-    pub fn new(reply_msg: Message) -> Self {
+    pub fn new(reply_msg: Message, display: &DisplayConfig, pacer: Arc<EditPacer>) -> Self {
         let cancel_msg_id = reply_msg.id;
+        let reasoning_mode = display.reasoning_mode(reply_msg.channel_id.get());
         Self {
             reply_msg,
             overflow: Vec::new(),
+            spare: VecDeque::new(),
             accumulated: String::new(),
             last_edit: Instant::now(),
             cancel_msg_id,
+            tools: Vec::new(),
+            answer_started: false,
+            hide_tool_status: display.hide_tool_status,
+            collapse_tool_status: display.collapse_tool_status,
+            reasoning: String::new(),
+            reasoning_mode,
+            transcript: String::new(),
+            overflow_mode: display.overflow,
+            overflow_after: display.overflow_after.max(1),
+            collapsed: false,
+            pacer,
+            dirty: false,
+            lost: HashSet::new(),
+        }
+    }
+
+    /// Streams into previously sent reply messages, reusing them in order
+    /// before sending new overflow messages.
+    pub fn reuse(
+        mut messages: Vec<Message>,
+        display: &DisplayConfig,
+        pacer: Arc<EditPacer>,
+    ) -> Option<Self> {
+        if messages.is_empty() {
+            return None;
         }
+        let first = messages.remove(0);
+        Some(Self {
+            spare: messages.into(),
+            ..Self::new(first, display, pacer)
+        })
+    }
+
+    /// Appends to an existing reply message, keeping its text.
+    pub fn resume(last: Message, display: &DisplayConfig, pacer: Arc<EditPacer>) -> Self {
+        let text: Vec<&str> = last
+            .content
+            .lines()
+            .rev()
+            .skip_while(|line| line.starts_with(TOOL_STATUS_PREFIX))
+            .collect();
+        let accumulated = text.into_iter().rev().collect::<Vec<_>>().join("\n");
+        Self {
+            transcript: accumulated.clone(),
+            accumulated,
+            ..Self::new(last, display, pacer)
+        }
+    }
+
+    pub fn messages(&self) -> Vec<Message> {
+        std::iter::once(&self.reply_msg)
+            .chain(&self.overflow)
+            .cloned()
+            .collect()
     }
 
     fn current_target(&mut self) -> &mut Message {
         self.overflow.last_mut().unwrap_or(&mut self.reply_msg)
     }
 
+    fn target_lost(&self) -> bool {
+        let target = self.overflow.last().unwrap_or(&self.reply_msg);
+        self.lost.contains(&target.id)
+    }
+
+    /// Stops editing the current message after an edit failed for good.
+    fn mark_target_lost(&mut self) {
+        let id = self.current_target().id;
+        self.lost.insert(id);
+        self.dirty = false;
+    }
+
+    fn tool_footer(&self) -> String {
+        if self.hide_tool_status
+            || self.tools.is_empty()
+            || (self.collapse_tool_status && self.answer_started)
+        {
+            return String::new();
+        }
+
+        let mut footer = String::new();
+        let skip = self.tools.len().saturating_sub(MAX_TOOL_STATUS_LINES);
+        for tool in &self.tools[skip..] {
+            footer.push('\n');
+            tool.format_line(&mut footer);
+        }
+        footer
+    }
+
+    fn is_thinking(&self) -> bool {
+        self.reasoning_mode != ReasoningMode::Hide
+            && !self.reasoning.is_empty()
+            && self.accumulated.is_empty()
+    }
+
+    fn render(&self) -> String {
+        let footer = self.tool_footer();
+        if self.collapsed {
+            let status = format!(
+                "\n{}: {} characters so far…",
+                LONG_RESPONSE_STATUS,
+                self.transcript.chars().count()
+            );
+            // split_streaming may add four characters to close a code block.
+            let room = DISCORD_MAX_LEN - status.chars().count() - footer.chars().count() - 5;
+            let (preview, _) = split_streaming(&self.accumulated, room);
+            format!("{}…{}{}", preview.trim_end(), status, footer)
+        } else if !self.accumulated.is_empty() {
+            format!("{}{}", self.accumulated, footer)
+        } else if !footer.is_empty() {
+            footer.trim_start().to_string()
+        } else if self.is_thinking() {
+            THINKING_PLACEHOLDER.to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    /// The answer streamed so far, across every message.
+    pub fn transcript(&self) -> &str {
+        &self.transcript
+    }
+
+    /// Characters of answer and reasoning streamed so far.
+    pub fn streamed_chars(&self) -> usize {
+        self.transcript.chars().count() + self.reasoning.chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accumulated.is_empty() && self.tool_footer().is_empty() && !self.is_thinking()
+    }
+
+    pub async fn push_reasoning(&mut self, ctx: &Context, text: &str) {
+        if self.reasoning_mode == ReasoningMode::Hide {
+            return;
+        }
+        let was_thinking = self.is_thinking();
+        self.reasoning.push_str(text);
+        if !was_thinking && self.is_thinking() {
+            self.dirty = true;
+            self.flush_edit(ctx).await;
+        }
+    }
+
+    pub async fn send_reasoning(&self, ctx: &Context) {
+        let reasoning = self.reasoning.trim();
+        if self.reasoning_mode == ReasoningMode::Hide || reasoning.is_empty() {
+            return;
+        }
+
+        let mut target = self.reply_msg.channel_id;
+        if self.reasoning_mode == ReasoningMode::Thread {
+            match target
+                .create_thread_from_message(
+                    &ctx.http,
+                    self.reply_msg.id,
+                    CreateThread::new("Reasoning"),
+                )
+                .await
+            {
+                Ok(thread) => target = thread.id,
+                Err(e) => warn!("Failed to create reasoning thread, using spoiler: {}", e),
+            }
+        }
+
+        let body = reasoning.replace("||", "| |");
+        let max_len = DISCORD_MAX_LEN - REASONING_HEADER.chars().count() - 4;
+        for (i, chunk) in split_message(&body, max_len).iter().enumerate() {
+            let header = if i == 0 { REASONING_HEADER } else { "" };
+            let content = if target == self.reply_msg.channel_id {
+                format!("{}||{}||", header, chunk)
+            } else {
+                format!("{}{}", header, chunk)
+            };
+            if let Err(e) = target.say(&ctx.http, content).await {
+                error!("Failed to send reasoning: {}", e);
+                break;
+            }
+        }
+    }
+
+    /// When content skipped by `flush_edit` can be shown: the time until the
+    /// channel's edit slot frees up, or `None` if nothing is pending.
+    pub fn pending_flush(&self) -> Option<Duration> {
+        if self.is_empty() || !self.dirty || self.target_lost() {
+            return None;
+        }
+        let channel_id = self.overflow.last().unwrap_or(&self.reply_msg).channel_id;
+        Some(self.pacer.ready_in(channel_id))
+    }
+
+    /// Shows the latest content if the channel's edit slot is free; otherwise
+    /// it stays pending until `pending_flush` says the slot is free.
     pub async fn flush_edit(&mut self, ctx: &Context) {
-        if self.accumulated.is_empty() {
+        if self.is_empty() || !self.dirty {
+            return;
+        }
+        if self.target_lost() {
+            self.dirty = false;
+            return;
+        }
+        let content = self.render();
+        let pacer = self.pacer.clone();
+        match pacer
+            .try_edit(
+                ctx,
+                self.current_target(),
+                EditMessage::new().content(&content),
+            )
+            .await
+        {
+            EditStatus::Done => {
+                self.dirty = false;
+                self.last_edit = Instant::now();
+            }
+            EditStatus::Deferred => {}
+            EditStatus::Failed => self.mark_target_lost(),
+        }
+    }
+
+    /// Like `flush_edit`, but waits and retries until the edit lands.
+    async fn flush_final(&mut self, ctx: &Context) {
+        if self.is_empty() || !self.dirty {
             return;
         }
-        let content = self.accumulated.clone();
-        let _ = self
-            .current_target()
-            .edit(ctx, EditMessage::new().content(&content))
+        let content = self.render();
+        self.edit_current(ctx, EditMessage::new().content(&content))
             .await;
+        self.dirty = false;
         self.last_edit = Instant::now();
     }
 
+    async fn edit_current(&mut self, ctx: &Context, builder: EditMessage) {
+        if self.target_lost() {
+            return;
+        }
+        let pacer = self.pacer.clone();
+        if pacer.edit(ctx, self.current_target(), builder).await == EditStatus::Failed {
+            self.mark_target_lost();
+        }
+    }
+
+    pub async fn push_tool_start(
+        &mut self,
+        ctx: &Context,
+        id: String,
+        name: String,
+        summary: String,
+    ) {
+        self.tools.push(ToolStatus {
+            id,
+            name,
+            summary,
+            started: Instant::now(),
+            outcome: None,
+        });
+        self.answer_started = false;
+        if !self.hide_tool_status {
+            self.dirty = true;
+            self.flush_edit(ctx).await;
+        }
+    }
+
+    pub async fn push_tool_end(&mut self, ctx: &Context, id: &str, success: bool) {
+        let status = match self.tools.iter().position(|t| t.id == id) {
+            Some(i) => self.tools.get_mut(i),
+            None => self.tools.iter_mut().find(|t| t.outcome.is_none()),
+        };
+        if let Some(status) = status {
+            status.outcome = Some((success, status.started.elapsed()));
+        }
+        if !self.hide_tool_status {
+            self.dirty = true;
+            self.flush_edit(ctx).await;
+        }
+    }
+
     pub async fn push_delta(
         &mut self,
         ctx: &Context,
@@ -54,24 +370,48 @@ impl StreamRelay {
         active_streams: &Mutex<HashMap<MessageId, StreamControl>>,
     ) {
         self.accumulated.push_str(text);
+        self.transcript.push_str(text);
+        self.dirty = true;
+        if !self.tools.is_empty() && !text.trim().is_empty() {
+            self.answer_started = true;
+        }
 
-        if self.accumulated.chars().count() > DISCORD_MAX_LEN {
-            let (finished_chunk, rest) = split_streaming(&self.accumulated, DISCORD_MAX_LEN);
+        let limit = DISCORD_MAX_LEN - self.tool_footer().chars().count();
+        if !self.collapsed
+            && self.accumulated.chars().count() > limit
+            && self.overflow_mode != OverflowMode::Messages
+            && self.overflow.len() + 1 >= self.overflow_after
+        {
+            self.collapsed = true;
+        }
+        if !self.collapsed && self.accumulated.chars().count() > limit {
+            let (finished_chunk, rest) = split_streaming(&self.accumulated, limit);
             self.accumulated = rest;
 
-            let _ = self
-                .current_target()
-                .edit(ctx, EditMessage::new().content(&finished_chunk))
+            self.edit_current(ctx, EditMessage::new().content(&finished_chunk))
                 .await;
 
-            if let Ok(new_msg) = origin_channel.say(&ctx.http, "…").await {
+            let next = match self.spare.pop_front() {
+                Some(mut spare) => {
+                    self.pacer
+                        .edit(
+                            ctx,
+                            &mut spare,
+                            EditMessage::new().content("…").embeds(Vec::new()),
+                        )
+                        .await;
+                    Ok(spare)
+                }
+                None => origin_channel.say(&ctx.http, "…").await,
+            };
+            if let Ok(new_msg) = next {
                 self.move_cancel_emoji(ctx, origin_channel, &new_msg, active_streams)
                     .await;
                 self.overflow.push(new_msg);
             }
         }
 
-        if self.last_edit.elapsed() >= EDIT_INTERVAL && !self.accumulated.is_empty() {
+        if self.last_edit.elapsed() >= EDIT_INTERVAL && !self.is_empty() {
             self.flush_edit(ctx).await;
         }
     }
@@ -101,17 +441,73 @@ impl StreamRelay {
 
     pub async fn finalize(&mut self, ctx: &Context, was_cancelled: bool) {
         if was_cancelled {
-            let display = if self.accumulated.is_empty() {
-                "*(Cancelled)*".to_string()
-            } else {
-                format!("{}\n\n*(Cancelled)*", self.accumulated.trim_end())
-            };
-            let _ = self
-                .current_target()
-                .edit(ctx, EditMessage::new().content(&display))
+            self.finalize_with_note(ctx, "*(Cancelled)*").await;
+            return;
+        }
+        for spare in self.spare.drain(..) {
+            let _ = spare.delete(ctx).await;
+        }
+        if self.collapsed {
+            self.deliver_long(ctx, None).await;
+        } else {
+            self.flush_final(ctx).await;
+        }
+    }
+
+    /// Ends an unfinished reply, keeping the text so far followed by `note`.
+    pub async fn finalize_with_note(&mut self, ctx: &Context, note: &str) {
+        for spare in self.spare.drain(..) {
+            let _ = spare.delete(ctx).await;
+        }
+        if self.collapsed {
+            self.deliver_long(ctx, Some(note)).await;
+            return;
+        }
+        let display = if self.accumulated.is_empty() {
+            note.to_string()
+        } else {
+            format!("{}\n\n{}", self.accumulated.trim_end(), note)
+        };
+        self.edit_current(ctx, EditMessage::new().content(&display))
+            .await;
+    }
+
+    /// Replaces the streamed messages with the whole answer rendered per
+    /// `[display].overflow`, reusing them in order.
+    async fn deliver_long(&mut self, ctx: &Context, note: Option<&str>) {
+        let parts = long_response_parts(
+            self.transcript.trim_end(),
+            note,
+            self.overflow_mode,
+            self.overflow_after,
+        );
+        let mut old: VecDeque<Message> = self.overflow.drain(..).collect();
+        let mut parts = parts.iter();
+        if let Some(first) = parts.next() {
+            self.pacer
+                .edit(ctx, &mut self.reply_msg, first.edit())
                 .await;
-        } else if !self.accumulated.is_empty() {
-            self.flush_edit(ctx).await;
+        }
+        for part in parts {
+            let sent = match old.pop_front() {
+                Some(mut msg) => {
+                    self.pacer.edit(ctx, &mut msg, part.edit()).await;
+                    Ok(msg)
+                }
+                None => {
+                    self.reply_msg
+                        .channel_id
+                        .send_message(&ctx.http, part.create())
+                        .await
+                }
+            };
+            match sent {
+                Ok(msg) => self.overflow.push(msg),
+                Err(e) => error!("Failed to send long response: {}", e),
+            }
+        }
+        for msg in old {
+            let _ = msg.delete(ctx).await;
         }
     }
 }
@@ -0,0 +1,373 @@
+use super::*;
+use crate::agent::{AttachmentInfo, UserInfo};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{ButtonStyle, CreateActionRow, CreateButton},
+    builder::{CreateInteractionResponse, CreateInteractionResponseMessage, EditMessage},
+    model::{
+        application::ComponentInteraction,
+        channel::Message,
+        id::{ChannelId, GuildId, MessageId, UserId},
+    },
+};
+use std::collections::{HashMap, VecDeque};
+use tracing::{error, info};
+
+const CUSTOM_ID_PREFIX: &str = "reply";
+const MAX_SESSIONS: usize = 500;
+const MAX_BUTTON_LABEL: usize = 80;
+
+/// Everything needed to run a request again.
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct ReplyRequest {
+    pub source_id: MessageId,
+    /// Channel of the source message when the reply went to a thread started on it.
+    #[serde(default)]
+    pub source_channel: Option<ChannelId>,
+    pub input: String,
+    pub user_info: UserInfo,
+    pub attachments: Vec<AttachmentInfo>,
+    pub discord_context: String,
+    #[serde(default)]
+    pub role: Role,
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub requester_id: UserId,
+    pub max_turns: Option<usize>,
+    /// The request came from a bot-owned thread whose history is in
+    /// `discord_context`.
+    #[serde(default)]
+    pub thread_history: bool,
+}
+
+/// Per-run overrides layered on top of a [`ReplyRequest`].
+#[derive(Clone, Default)]
+pub(super) struct RunOptions {
+    pub replace_turn: Option<String>,
+    pub previous_response: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Clone)]
+pub(super) struct ReplySession {
+    pub request: Arc<ReplyRequest>,
+    pub messages: Vec<Message>,
+    pub response: String,
+    /// Memory turn of the answer; empty if it was never stored.
+    pub turn_id: String,
+    pub model: Option<String>,
+}
+
+/// Finished replies that can still be regenerated or continued, keyed by the
+/// first reply message. The oldest sessions are dropped first.
+#[derive(Default)]
+pub(super) struct ReplySessions {
+    sessions: HashMap<MessageId, ReplySession>,
+    by_source: HashMap<MessageId, MessageId>,
+    order: VecDeque<MessageId>,
+}
+
+impl ReplySessions {
+    pub fn insert(&mut self, session: ReplySession) {
+        let Some(key) = session.messages.first().map(|m| m.id) else {
+            return;
+        };
+        self.by_source.insert(session.request.source_id, key);
+        if self.sessions.insert(key, session).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > MAX_SESSIONS {
+            if let Some(oldest) = self.order.pop_front()
+                && let Some(session) = self.sessions.remove(&oldest)
+            {
+                self.by_source.remove(&session.request.source_id);
+            }
+        }
+    }
+
+    /// Finds the session answering the given user message.
+    pub fn find_by_source(&self, source_id: MessageId) -> Option<MessageId> {
+        self.by_source.get(&source_id).copied()
+    }
+
+    pub fn get(&self, key: MessageId) -> Option<&ReplySession> {
+        self.sessions.get(&key)
+    }
+
+    /// Takes a session out while it is being re-run so a second click cannot
+    /// start a parallel run.
+    pub fn take(&mut self, key: MessageId) -> Option<ReplySession> {
+        self.order.retain(|id| *id != key);
+        let session = self.sessions.remove(&key)?;
+        self.by_source.remove(&session.request.source_id);
+        Some(session)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplyAction {
+    Regenerate,
+    Continue,
+    OtherModel,
+}
+
+fn parse_custom_id(custom_id: &str) -> Option<(ReplyAction, MessageId)> {
+    let mut parts = custom_id.splitn(3, ':');
+    if parts.next()? != CUSTOM_ID_PREFIX {
+        return None;
+    }
+    let action = match parts.next()? {
+        "regenerate" => ReplyAction::Regenerate,
+        "continue" => ReplyAction::Continue,
+        "model" => ReplyAction::OtherModel,
+        _ => return None,
+    };
+    let id = parts.next()?.parse::<u64>().ok().filter(|id| *id != 0)?;
+    Some((action, MessageId::new(id)))
+}
+
+impl Handler {
+    /// The model the "other model" button switches to, if one is configured.
+    fn other_model(&self, current: Option<&str>) -> Option<String> {
+        let alternate = self.config.model.alternate.as_deref()?;
+        match current {
+            Some(_) => Some(self.config.api.model.clone()),
+            None => Some(alternate.to_string()),
+        }
+    }
+
+    fn reply_buttons(&self, session: &ReplySession) -> Vec<CreateActionRow> {
+        let Some(key) = session.messages.first().map(|m| m.id) else {
+            return Vec::new();
+        };
+        let mut buttons = vec![
+            CreateButton::new(format!("{}:regenerate:{}", CUSTOM_ID_PREFIX, key))
+                .label("Regenerate")
+                .emoji('🔄')
+                .style(ButtonStyle::Secondary),
+            CreateButton::new(format!("{}:continue:{}", CUSTOM_ID_PREFIX, key))
+                .label("Continue")
+                .emoji('⏩')
+                .style(ButtonStyle::Secondary),
+        ];
+        if let Some(model) = self.other_model(session.model.as_deref()) {
+            let label: String = format!("Use {}", model)
+                .chars()
+                .take(MAX_BUTTON_LABEL)
+                .collect();
+            buttons.push(
+                CreateButton::new(format!("{}:model:{}", CUSTOM_ID_PREFIX, key))
+                    .label(label)
+                    .style(ButtonStyle::Secondary),
+            );
+        }
+        if self.config.feedback.enabled && !session.turn_id.is_empty() {
+            buttons.extend(feedback_buttons(&session.turn_id));
+        }
+        vec![CreateActionRow::Buttons(buttons)]
+    }
+
+    async fn set_reply_buttons(&self, ctx: &Context, session: &mut ReplySession, enabled: bool) {
+        let components = if enabled {
+            self.reply_buttons(session)
+        } else {
+            Vec::new()
+        };
+        if let Some(last) = session.messages.last_mut()
+            && let Err(e) = last
+                .edit(ctx, EditMessage::new().components(components))
+                .await
+        {
+            error!("Failed to update reply buttons: {}", e);
+        }
+    }
+
+    /// Attaches the reply buttons and remembers the session for them.
+    pub(super) async fn store_session(&self, ctx: &Context, mut session: ReplySession) {
+        self.set_reply_buttons(ctx, &mut session, true).await;
+        self.sessions.lock().await.insert(session);
+    }
+
+    async fn reply_ephemeral(&self, ctx: &Context, component: &ComponentInteraction, text: &str) {
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(text)
+                .ephemeral(true),
+        );
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            error!("Failed to respond to reply button: {}", e);
+        }
+    }
+
+    pub(super) async fn handle_reply_button(
+        &self,
+        ctx: &Context,
+        component: &ComponentInteraction,
+    ) {
+        let Some((action, key)) = parse_custom_id(&component.data.custom_id) else {
+            return;
+        };
+
+        if self.is_shutting_down() {
+            return self
+                .reply_ephemeral(ctx, component, "The bot is restarting. Try again shortly.")
+                .await;
+        }
+
+        let user_id = component.user.id;
+        let is_admin = self
+            .member_role(ctx, user_id, component.guild_id, component.member.as_ref())
+            .is_admin();
+        let taken = {
+            let mut sessions = self.sessions.lock().await;
+            match sessions.get(key) {
+                None => Err("This reply is being updated or can no longer be changed."),
+                Some(s) if user_id != s.request.requester_id && !is_admin => {
+                    Err("Only the person who asked can do that.")
+                }
+                Some(_) => sessions.take(key).ok_or(""),
+            }
+        };
+        let mut session = match taken {
+            Ok(session) => session,
+            Err(reason) => return self.reply_ephemeral(ctx, component, reason).await,
+        };
+
+        let request = session.request.clone();
+        if !is_admin && let Err(e) = self.limiter.admit(user_id, request.guild_id) {
+            self.sessions.lock().await.insert(session);
+            return self.reply_ephemeral(ctx, component, &e.to_string()).await;
+        }
+
+        if let Err(e) = component
+            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+            .await
+        {
+            error!("Failed to acknowledge reply button: {}", e);
+        }
+        info!("Reply {} requested {:?} by {}", key, action, user_id);
+
+        self.rerun_session(ctx, session, request, action, user_id)
+            .await;
+    }
+
+    /// Re-answers an edited message into its existing reply, replacing the
+    /// stored turn. Edits outside `[triggers].edit_window` are ignored.
+    pub(super) async fn rerun_edited(&self, ctx: &Context, source_id: MessageId) {
+        let Some(key) = self.sessions.lock().await.find_by_source(source_id) else {
+            return;
+        };
+        let Some(session) = self.sessions.lock().await.take(key) else {
+            return;
+        };
+
+        let request = session.request.clone();
+        let edited = self.edited_request(ctx, &request).await;
+        let Some(edited) = edited.filter(|e| e.input != request.input) else {
+            self.sessions.lock().await.insert(session);
+            return;
+        };
+
+        if !request.role.is_admin()
+            && let Err(e) = self.limiter.admit(request.requester_id, request.guild_id)
+        {
+            info!("Rejected edit re-run from {}: {}", request.requester_id, e);
+            self.sessions.lock().await.insert(session);
+            return;
+        }
+
+        info!("Re-running reply {} for edited message {}", key, source_id);
+        let requester_id = request.requester_id;
+        self.rerun_session(
+            ctx,
+            session,
+            Arc::new(edited),
+            ReplyAction::Regenerate,
+            requester_id,
+        )
+        .await;
+    }
+
+    async fn rerun_session(
+        &self,
+        ctx: &Context,
+        mut session: ReplySession,
+        request: Arc<ReplyRequest>,
+        action: ReplyAction,
+        user_id: UserId,
+    ) {
+        self.set_reply_buttons(ctx, &mut session, false).await;
+
+        let model = match action {
+            ReplyAction::OtherModel => self.other_model(session.model.as_deref()),
+            _ => session.model.clone(),
+        };
+        let model = model.filter(|m| *m != self.config.api.model);
+
+        let (relay, kept, previous) = if action == ReplyAction::Continue {
+            let mut kept = session.messages.clone();
+            let Some(last) = kept.pop() else {
+                return;
+            };
+            // A long answer sent as embeds or a file continues in a new message.
+            let relay = if last.embeds.is_empty() && last.attachments.is_empty() {
+                StreamRelay::resume(last, &self.config.display, self.pacer.clone())
+            } else {
+                let Ok(next) = last.channel_id.say(&ctx.http, "…").await else {
+                    return;
+                };
+                kept.push(last);
+                StreamRelay::new(next, &self.config.display, self.pacer.clone())
+            };
+            (relay, kept, Some(session.response.clone()))
+        } else {
+            let Some(mut relay) = StreamRelay::reuse(
+                session.messages.clone(),
+                &self.config.display,
+                self.pacer.clone(),
+            ) else {
+                return;
+            };
+            let _ = relay
+                .reply_msg
+                .edit(
+                    ctx,
+                    EditMessage::new()
+                        .content("…")
+                        .embeds(Vec::new())
+                        .remove_all_attachments(),
+                )
+                .await;
+            (relay, Vec::new(), None)
+        };
+
+        let options = RunOptions {
+            replace_turn: Some(session.turn_id.clone()).filter(|id| !id.is_empty()),
+            previous_response: previous.clone(),
+            model: model.clone(),
+        };
+        let Some((request, response, mut relay)) =
+            self.stream_reply(ctx, request, relay, options).await
+        else {
+            self.store_session(ctx, session).await;
+            return;
+        };
+
+        self.send_response_files_and_notify(
+            ctx,
+            request.channel_id,
+            user_id,
+            &mut relay,
+            &response,
+        )
+        .await;
+        relay.send_reasoning(ctx).await;
+
+        session.request = request;
+        session.messages = kept.into_iter().chain(relay.messages()).collect();
+        session.response = format!("{}{}", previous.unwrap_or_default(), response.text);
+        session.turn_id = response.turn_id;
+        session.model = model;
+        self.store_session(ctx, session).await;
+    }
+}
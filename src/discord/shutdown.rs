@@ -0,0 +1,170 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::MessageId;
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+const INTERRUPTED_FILE: &str = "interrupted_replies.json";
+const INTERRUPTED_NOTE: &str = "*(Interrupted by restart)*";
+/// How long aborted streams get to write their final edit.
+const FINALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A reply cut off by a shutdown, restored with reply buttons on the next start.
+#[derive(Serialize, Deserialize)]
+pub(super) struct InterruptedReply {
+    request: ReplyRequest,
+    message_ids: Vec<MessageId>,
+    response: String,
+    model: Option<String>,
+}
+
+/// Counts a running `stream_reply` until dropped.
+pub(super) struct StreamGuard<'a>(&'a AtomicUsize);
+
+impl Drop for StreamGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Lets `main` drain the bot before the process exits.
+pub struct ShutdownHandle {
+    pub(super) handler: Arc<Handler>,
+}
+
+impl ShutdownHandle {
+    /// Stops accepting messages, waits up to the grace period for active
+    /// replies, then interrupts the rest. Returns how many were interrupted.
+    pub async fn shutdown(&self) -> usize {
+        self.handler.shutdown().await
+    }
+}
+
+impl Handler {
+    fn interrupted_path(&self) -> PathBuf {
+        self.config.storage.data_dir.join(INTERRUPTED_FILE)
+    }
+
+    pub(super) fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Acquire)
+    }
+
+    pub(super) fn track_stream(&self) -> StreamGuard<'_> {
+        self.running_streams.fetch_add(1, Ordering::AcqRel);
+        StreamGuard(&self.running_streams)
+    }
+
+    /// Marks the reply as interrupted and remembers it for a retry after restart.
+    pub(super) async fn checkpoint(
+        &self,
+        ctx: &Context,
+        request: &ReplyRequest,
+        relay: &mut StreamRelay,
+        options: &RunOptions,
+    ) {
+        relay.finalize_with_note(ctx, INTERRUPTED_NOTE).await;
+        self.interrupted.lock().await.push(InterruptedReply {
+            request: request.clone(),
+            message_ids: relay.messages().iter().map(|m| m.id).collect(),
+            response: relay.transcript().to_string(),
+            model: options.model.clone(),
+        });
+    }
+
+    async fn shutdown(&self) -> usize {
+        self.shutting_down.store(true, Ordering::Release);
+        // Queued requests are interrupted right away rather than started in
+        // the grace period.
+        let queued = self.queue.cancel_all();
+
+        let grace = Duration::from_secs(self.config.shutdown.grace_period);
+        let deadline = Instant::now() + grace;
+        while self.running_streams.load(Ordering::Acquire) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(STREAM_POLL_TIMEOUT).await;
+        }
+
+        let remaining: Vec<StreamControl> = self
+            .active_streams
+            .lock()
+            .await
+            .drain()
+            .map(|(_, ctrl)| ctrl)
+            .collect();
+        if !remaining.is_empty() || queued > 0 {
+            info!(
+                "Interrupting {} active and {} queued replies",
+                remaining.len(),
+                queued
+            );
+        }
+        for ctrl in &remaining {
+            ctrl.interrupted.store(true, Ordering::Release);
+            ctrl.cancelled.store(true, Ordering::Release);
+            ctrl.abort_handle.abort();
+        }
+
+        let deadline = Instant::now() + FINALIZE_TIMEOUT;
+        while self.running_streams.load(Ordering::Acquire) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(STREAM_POLL_TIMEOUT).await;
+        }
+
+        let interrupted = std::mem::take(&mut *self.interrupted.lock().await);
+        let count = interrupted.len();
+        if count > 0 {
+            match serde_json::to_string(&interrupted) {
+                Ok(json) => {
+                    if let Err(e) = tokio::fs::write(self.interrupted_path(), json).await {
+                        error!("Failed to save interrupted replies: {}", e);
+                    }
+                }
+                Err(e) => error!("Failed to serialize interrupted replies: {}", e),
+            }
+        }
+        count
+    }
+
+    /// Offers reply buttons on answers interrupted by the last shutdown.
+    pub(super) async fn restore_interrupted(&self, ctx: &Context) {
+        let path = self.interrupted_path();
+        let Ok(json) = tokio::fs::read_to_string(&path).await else {
+            return;
+        };
+        let _ = tokio::fs::remove_file(&path).await;
+        let replies: Vec<InterruptedReply> = match serde_json::from_str(&json) {
+            Ok(replies) => replies,
+            Err(e) => {
+                warn!("Ignoring unreadable {}: {}", INTERRUPTED_FILE, e);
+                return;
+            }
+        };
+
+        let count = replies.len();
+        for reply in replies {
+            let mut messages = Vec::with_capacity(reply.message_ids.len());
+            for id in &reply.message_ids {
+                if let Ok(msg) = reply.request.channel_id.message(&ctx.http, *id).await {
+                    messages.push(msg);
+                }
+            }
+            if messages.is_empty() {
+                continue;
+            }
+            self.store_session(
+                ctx,
+                ReplySession {
+                    request: Arc::new(reply.request),
+                    messages,
+                    response: reply.response,
+                    turn_id: String::new(),
+                    model: reply.model,
+                },
+            )
+            .await;
+        }
+        info!("Restored reply buttons on {} interrupted answers", count);
+    }
+}
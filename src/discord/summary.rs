@@ -0,0 +1,424 @@
+use super::*;
+use anyhow::Context as _;
+use serenity::{
+    all::Http,
+    builder::{CreateInteractionResponseFollowup, EditInteractionResponse, GetMessages},
+    model::{
+        application::CommandInteraction,
+        channel::Message,
+        id::{ChannelId, GuildId, MessageId},
+    },
+};
+use std::fmt::Write;
+use tracing::{error, info};
+
+/// Upper bound on messages read for one summary.
+const MAX_HISTORY: usize = 2000;
+const DEFAULT_MESSAGES: usize = 100;
+const DEFAULT_SCHEDULED_HOURS: u64 = 24;
+/// Characters of history sent to the model per call.
+const CHUNK_CHARS: usize = 12_000;
+const MAX_MESSAGE_CHARS: usize = 600;
+
+const MAP_INSTRUCTION: &str = "You are reading one part of a Discord channel's history. \
+Write concise bullet notes of the topics, decisions, open questions and action items in it. \
+Each message starts with its number in square brackets; cite the messages that support each \
+note the same way, like [12].";
+const FINAL_INSTRUCTION: &str = "Summarize this Discord channel history for someone catching \
+up. Group related points by topic and lead with decisions and action items. Use at most 15 \
+bullets. Cite the key messages behind each point by their number in square brackets, like \
+[12], at most three per point. Reply with the summary only.";
+const REDUCE_INSTRUCTION: &str = "Combine these notes on parts of a Discord channel's history \
+into one summary for someone catching up. Group related points by topic and lead with \
+decisions and action items. Use at most 15 bullets. Keep the [n] citations of the key \
+messages, at most three per point. Reply with the summary only.";
+
+/// Which part of a channel's history to summarize.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SummaryRange {
+    Last(usize),
+    SinceUser(UserId),
+    Hours(u64),
+}
+
+impl SummaryRange {
+    fn describe(&self, count: usize) -> String {
+        match self {
+            Self::Last(_) => format!("the last {} messages", count),
+            Self::SinceUser(_) => format!("{} messages since your last message", count),
+            Self::Hours(hours) => format!("the last {}h ({} messages)", hours, count),
+        }
+    }
+}
+
+pub(crate) struct ChannelSummary {
+    pub text: String,
+    pub total_tokens: u64,
+}
+
+/// Pages backwards through the channel until the range is covered. Returns
+/// the messages oldest first.
+async fn fetch_history(
+    http: &Http,
+    channel_id: ChannelId,
+    range: SummaryRange,
+) -> Result<Vec<Message>> {
+    let cutoff = match range {
+        SummaryRange::Hours(hours) => Some(chrono::Utc::now().timestamp() - hours as i64 * 3600),
+        _ => None,
+    };
+    let limit = match range {
+        SummaryRange::Last(count) => count.min(MAX_HISTORY),
+        _ => MAX_HISTORY,
+    };
+
+    let mut history = Vec::new();
+    let mut before: Option<MessageId> = None;
+    'paging: loop {
+        let mut request = GetMessages::new().limit(100);
+        if let Some(id) = before {
+            request = request.before(id);
+        }
+        let page = channel_id
+            .messages(http, request)
+            .await
+            .context("Failed to read channel history")?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(last.id);
+
+        for msg in page {
+            let done = history.len() >= limit
+                || matches!(range, SummaryRange::SinceUser(user) if msg.author.id == user)
+                || cutoff.is_some_and(|t| msg.timestamp.unix_timestamp() < t);
+            if done {
+                break 'paging;
+            }
+            history.push(msg);
+        }
+    }
+
+    history.retain(|m| {
+        !m.content.trim().is_empty() || !m.attachments.is_empty() || !m.embeds.is_empty()
+    });
+    history.reverse();
+    Ok(history)
+}
+
+fn format_line(n: usize, msg: &Message) -> String {
+    let time = chrono::DateTime::from_timestamp(msg.timestamp.unix_timestamp(), 0)
+        .map(|dt| dt.format("%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+    let author = msg
+        .author
+        .global_name
+        .as_deref()
+        .unwrap_or(&msg.author.name);
+    let mut content: String = msg.content.trim().chars().take(MAX_MESSAGE_CHARS).collect();
+    if msg.content.trim().chars().count() > MAX_MESSAGE_CHARS {
+        content.push('…');
+    }
+    for embed in &msg.embeds {
+        if let Some(title) = embed.title.as_deref().or(embed.description.as_deref()) {
+            let _ = write!(content, " [embed: {}]", title);
+        }
+    }
+    if !msg.attachments.is_empty() {
+        let _ = write!(content, " [{} attachment(s)]", msg.attachments.len());
+    }
+    format!(
+        "[{}] {} {}: {}\n",
+        n,
+        time,
+        author,
+        content.replace('\n', " ")
+    )
+}
+
+/// Packs texts into chunks of at most `max_chars` (a single longer text
+/// gets its own chunk).
+fn pack(texts: impl IntoIterator<Item = String>, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for text in texts {
+        if !current.is_empty() && current.len() + text.len() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(&text);
+        current.push('\n');
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Replaces `[n]` citations with jump links to the cited messages.
+fn link_citations(
+    text: &str,
+    ids: &[MessageId],
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+) -> String {
+    let guild = guild_id.map_or("@me".to_string(), |g| g.to_string());
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let digits = after.chars().take_while(char::is_ascii_digit).count();
+        let cited = after[digits..]
+            .starts_with(']')
+            .then(|| after[..digits].parse::<usize>().ok())
+            .flatten()
+            .and_then(|n| Some((n, ids.get(n.checked_sub(1)?)?)));
+        match cited {
+            Some((n, id)) => {
+                let _ = write!(
+                    out,
+                    "[#{}](<https://discord.com/channels/{}/{}/{}>)",
+                    n, guild, channel_id, id
+                );
+                rest = &after[digits + 1..];
+            }
+            None => {
+                out.push('[');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Summarizes part of a channel's history: each chunk of messages is
+/// summarized on its own, then the notes are merged until one summary is left.
+pub(crate) async fn summarize_channel(
+    http: &Http,
+    agent: &dyn Agent,
+    channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    range: SummaryRange,
+) -> Result<ChannelSummary> {
+    let history = fetch_history(http, channel_id, range).await?;
+    if history.is_empty() {
+        return Ok(ChannelSummary {
+            text: "No messages to summarize.".to_string(),
+            total_tokens: 0,
+        });
+    }
+    info!(
+        "Summarizing {} messages in channel {}",
+        history.len(),
+        channel_id
+    );
+
+    let ids: Vec<MessageId> = history.iter().map(|m| m.id).collect();
+    let chunks = pack(
+        history
+            .iter()
+            .enumerate()
+            .map(|(i, m)| format_line(i + 1, m)),
+        CHUNK_CHARS,
+    );
+
+    let mut total_tokens = 0;
+    let summary = if chunks.len() == 1 {
+        let (text, tokens) = agent.complete(FINAL_INSTRUCTION, &chunks[0]).await?;
+        total_tokens += tokens;
+        text
+    } else {
+        let mut notes = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            let (text, tokens) = agent.complete(MAP_INSTRUCTION, chunk).await?;
+            total_tokens += tokens;
+            notes.push(text);
+        }
+        loop {
+            let mut groups = pack(notes.iter().cloned(), CHUNK_CHARS);
+            if groups.len() == notes.len() && notes.len() > 1 {
+                groups = notes.chunks(2).map(|pair| pair.join("\n")).collect();
+            }
+            let last = groups.len() == 1;
+            notes.clear();
+            for group in &groups {
+                let (text, tokens) = agent.complete(REDUCE_INSTRUCTION, group).await?;
+                total_tokens += tokens;
+                notes.push(text);
+            }
+            if last {
+                break notes.pop().unwrap_or_default();
+            }
+        }
+    };
+
+    Ok(ChannelSummary {
+        text: format!(
+            "**Summary of {}**\n{}",
+            range.describe(history.len()),
+            link_citations(summary.trim(), &ids, channel_id, guild_id)
+        ),
+        total_tokens,
+    })
+}
+
+/// Summarizes the last `hours` of a channel and posts it there. Used by
+/// scheduled summary tasks.
+pub(crate) async fn post_channel_summary(
+    http: &Http,
+    agent: &dyn Agent,
+    channel_id: u64,
+    hours: u64,
+) -> Result<()> {
+    let channel_id = ChannelId::new(channel_id);
+    let guild_id = channel_id
+        .to_channel(http)
+        .await
+        .ok()
+        .and_then(|c| c.guild())
+        .map(|c| c.guild_id);
+    let summary = summarize_channel(
+        http,
+        agent,
+        channel_id,
+        guild_id,
+        SummaryRange::Hours(hours),
+    )
+    .await?;
+    for chunk in split_message(&summary.text, DISCORD_MAX_LEN) {
+        channel_id.say(http, chunk).await?;
+    }
+    Ok(())
+}
+
+impl Handler {
+    pub(super) async fn handle_summarize(&self, ctx: &Context, cmd: &CommandInteraction) {
+        let options = cmd.data.options();
+        if !self
+            .channel_allowed(ctx, cmd.guild_id, cmd.channel_id)
+            .await
+        {
+            return self
+                .respond_ephemeral(ctx, cmd, "This command isn't available here.")
+                .await;
+        }
+
+        let hours = int_option(&options, "hours").map(|h| h.max(1) as u64);
+        if let Some(cron) = string_option(&options, "schedule") {
+            let reply = if !self.command_role(ctx, cmd).is_operator() {
+                "Only operators and admins can schedule summaries.".to_string()
+            } else {
+                let hours = hours.unwrap_or(DEFAULT_SCHEDULED_HOURS);
+                let timezone = self
+                    .vector_db
+                    .user_preferences(cmd.user.id.get())
+                    .await
+                    .ok()
+                    .and_then(|p| p.timezone);
+                match self
+                    .scheduler
+                    .add_summary_task(cron, hours, cmd.channel_id.get(), timezone)
+                    .await
+                {
+                    Ok(id) => format!(
+                        "Scheduled task `{}`: a summary of the last {}h at `{}`.",
+                        id, hours, cron
+                    ),
+                    Err(e) => format!("Couldn't schedule the summary: {}", e),
+                }
+            };
+            return self.respond_ephemeral(ctx, cmd, &reply).await;
+        }
+
+        let range = if bool_option(&options, "since_my_last").unwrap_or(false) {
+            SummaryRange::SinceUser(cmd.user.id)
+        } else if let Some(hours) = hours {
+            SummaryRange::Hours(hours)
+        } else {
+            let count = int_option(&options, "messages").map_or(DEFAULT_MESSAGES, |n| n as usize);
+            SummaryRange::Last(count.clamp(1, MAX_HISTORY))
+        };
+
+        let is_admin = self.command_role(ctx, cmd).is_admin();
+        if !is_admin && let Err(e) = self.limiter.admit(cmd.user.id, cmd.guild_id) {
+            return self.respond_ephemeral(ctx, cmd, &e.to_string()).await;
+        }
+
+        if let Err(e) = cmd.defer(&ctx.http).await {
+            error!("Failed to defer /summarize: {}", e);
+            return;
+        }
+
+        // Waits behind answers running in the channel, like any other request.
+        let _running = self.track_stream();
+        let _slot = match self.queue.join(
+            MessageId::new(cmd.id.get()),
+            cmd.user.id,
+            cmd.channel_id,
+            cmd.guild_id,
+        ) {
+            Admission::Ready(slot) => slot,
+            Admission::Queued(mut ticket) => loop {
+                match ticket.next().await {
+                    QueueUpdate::Ready(slot) if !self.is_shutting_down() => break slot,
+                    QueueUpdate::Position(_) => {}
+                    QueueUpdate::Ready(_) | QueueUpdate::Cancelled => return,
+                }
+            },
+        };
+        let _permit = if is_admin {
+            None
+        } else {
+            match self.limiter.occupy(cmd.user.id, cmd.channel_id) {
+                Ok(permit) => Some(permit),
+                Err(e) => {
+                    let _ = cmd
+                        .edit_response(
+                            &ctx.http,
+                            EditInteractionResponse::new().content(e.to_string()),
+                        )
+                        .await;
+                    return;
+                }
+            }
+        };
+
+        let text = match summarize_channel(
+            &ctx.http,
+            self.agent.as_ref(),
+            cmd.channel_id,
+            cmd.guild_id,
+            range,
+        )
+        .await
+        {
+            Ok(summary) => {
+                self.limiter
+                    .record_usage(cmd.user.id, cmd.guild_id, summary.total_tokens);
+                summary.text
+            }
+            Err(e) => {
+                error!("/summarize failed: {}", e);
+                format!("Couldn't summarize this channel: {}", e)
+            }
+        };
+
+        let mut chunks = split_message(&text, DISCORD_MAX_LEN).into_iter();
+        if let Err(e) = cmd
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new().content(chunks.next().unwrap_or_default()),
+            )
+            .await
+        {
+            error!("Failed to respond to /summarize: {}", e);
+        }
+        for chunk in chunks {
+            let followup = CreateInteractionResponseFollowup::new().content(chunk);
+            if let Err(e) = cmd.create_followup(&ctx.http, followup).await {
+                error!("Failed to send /summarize follow-up: {}", e);
+            }
+        }
+    }
+}
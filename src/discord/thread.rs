@@ -0,0 +1,62 @@
+use super::*;
+use serenity::{
+    builder::CreateThread,
+    model::{
+        channel::{AutoArchiveDuration, ChannelType, GuildChannel, Message},
+        id::{ChannelId, MessageId, UserId},
+    },
+};
+use tracing::warn;
+
+const MAX_THREAD_NAME: usize = 100;
+const DEFAULT_THREAD_NAME: &str = "Conversation";
+
+/// The thread a guild message was sent in, if any.
+pub(super) async fn current_thread(ctx: &Context, msg: &Message) -> Option<GuildChannel> {
+    msg.guild_id?;
+    let channel = msg.channel_id.to_channel(ctx).await.ok()?.guild()?;
+    matches!(
+        channel.kind,
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread
+    )
+    .then_some(channel)
+}
+
+pub(super) fn is_bot_thread(thread: &GuildChannel, bot_id: UserId) -> bool {
+    thread.owner_id == Some(bot_id)
+}
+
+/// Names a thread after the first line of the question.
+pub(super) fn thread_name(input: &str) -> String {
+    let line = input
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or(DEFAULT_THREAD_NAME);
+    if line.chars().count() > MAX_THREAD_NAME {
+        let truncated: String = line.chars().take(MAX_THREAD_NAME - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Starts a bot-owned thread on a message.
+pub(super) async fn start_thread(
+    ctx: &Context,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    name: String,
+) -> Option<GuildChannel> {
+    let builder = CreateThread::new(name).auto_archive_duration(AutoArchiveDuration::OneDay);
+    match channel_id
+        .create_thread_from_message(&ctx.http, message_id, builder)
+        .await
+    {
+        Ok(thread) => Some(thread),
+        Err(e) => {
+            warn!("Failed to start thread on message {}: {}", message_id, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use super::Handler;
+use crate::config::TriggersConfig;
+use serenity::{
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId, UserId},
+    },
+    prelude::*,
+};
+
+fn strip_mentions(content: &str, bot_id: UserId) -> String {
+    content
+        .replace(&format!("<@{}>", bot_id), "")
+        .replace(&format!("<@!{}>", bot_id), "")
+}
+
+/// Decides whether a message should be answered and returns the prompt text
+/// with the mention or prefix removed. `parent_id` is the parent channel when
+/// the message was sent in a thread. Every message in a bot-owned thread
+/// (`in_bot_thread`) is a follow-up.
+pub(super) fn match_trigger(
+    triggers: &TriggersConfig,
+    msg: &Message,
+    parent_id: Option<u64>,
+    bot_id: UserId,
+    in_bot_thread: bool,
+) -> Option<String> {
+    let guild_id = msg.guild_id.map(|g| g.get());
+    let channel_id = msg.channel_id.get();
+    if !triggers.is_allowed(guild_id, channel_id, parent_id) {
+        return None;
+    }
+
+    let modes = triggers.modes(guild_id, channel_id, parent_id);
+    let content = strip_mentions(&msg.content, bot_id);
+    let mentioned = content.len() != msg.content.len();
+    let prefixed = modes
+        .prefix
+        .as_deref()
+        .and_then(|prefix| content.trim_start().strip_prefix(prefix))
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+    let replied = msg
+        .referenced_message
+        .as_ref()
+        .is_some_and(|m| m.author.id == bot_id);
+
+    let triggered = (modes.mention && mentioned)
+        || prefixed.is_some()
+        || (modes.reply_to_bot && replied)
+        || modes.all_messages
+        || in_bot_thread;
+    if !triggered {
+        return None;
+    }
+
+    Some(prefixed.unwrap_or(&content).trim().to_string())
+}
+
+impl Handler {
+    /// The parent channel when `channel_id` is a thread in a guild.
+    pub(super) async fn parent_channel(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+    ) -> Option<u64> {
+        guild_id?;
+        self.thread_parents
+            .parent_of(&ctx.http, channel_id.get())
+            .await
+    }
+
+    /// Whether `[triggers]` lets the bot respond in a channel, or in the
+    /// thread's parent channel.
+    pub(super) async fn channel_allowed(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+    ) -> bool {
+        let parent_id = self.parent_channel(ctx, guild_id, channel_id).await;
+        self.config
+            .triggers
+            .is_allowed(guild_id.map(|g| g.get()), channel_id.get(), parent_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TriggerOverride;
+
+    const BOT: UserId = UserId::new(10);
+    const GUILD: u64 = 1;
+    const CHANNEL: u64 = 2;
+    const THREAD: u64 = 3;
+
+    fn message(content: &str, guild: bool, channel_id: u64) -> Message {
+        let mut msg = Message::default();
+        msg.content = content.to_string();
+        msg.channel_id = ChannelId::new(channel_id);
+        msg.guild_id = guild.then(|| GuildId::new(GUILD));
+        msg
+    }
+
+    fn in_guild(content: &str) -> Message {
+        message(content, true, CHANNEL)
+    }
+
+    #[test]
+    fn mention_is_stripped() {
+        let triggers = TriggersConfig::default();
+        let msg = in_guild("<@10> hello");
+        assert_eq!(
+            match_trigger(&triggers, &msg, None, BOT, false).as_deref(),
+            Some("hello")
+        );
+        assert_eq!(
+            match_trigger(&triggers, &in_guild("hello"), None, BOT, false),
+            None
+        );
+    }
+
+    #[test]
+    fn prefix_needs_a_word_boundary() {
+        let triggers = TriggersConfig {
+            prefix: Some("!claw".to_string()),
+            ..Default::default()
+        };
+        let matched =
+            |content: &str| match_trigger(&triggers, &in_guild(content), None, BOT, false);
+
+        assert_eq!(
+            matched("!claw what time is it").as_deref(),
+            Some("what time is it")
+        );
+        assert_eq!(matched("  !claw").as_deref(), Some(""));
+        assert_eq!(matched("!clawback is a word"), None);
+        assert_eq!(matched("say !claw"), None);
+    }
+
+    #[test]
+    fn dms_need_a_mention_unless_enabled() {
+        let dm = message("hello", false, CHANNEL);
+        let mention = message("<@10> hello", false, CHANNEL);
+
+        let triggers = TriggersConfig::default();
+        assert_eq!(match_trigger(&triggers, &dm, None, BOT, false), None);
+        assert!(match_trigger(&triggers, &mention, None, BOT, false).is_some());
+
+        let triggers = TriggersConfig {
+            dms: true,
+            ..Default::default()
+        };
+        assert!(match_trigger(&triggers, &dm, None, BOT, false).is_some());
+    }
+
+    #[test]
+    fn every_message_in_a_bot_thread_is_a_follow_up() {
+        let triggers = TriggersConfig::default();
+        let msg = message("and then?", true, THREAD);
+        assert!(match_trigger(&triggers, &msg, Some(CHANNEL), BOT, true).is_some());
+    }
+
+    #[test]
+    fn threads_follow_their_parent_channel_lists() {
+        let msg = message("<@10> hi", true, THREAD);
+
+        let allowed = TriggersConfig {
+            allowed_channels: vec![CHANNEL],
+            ..Default::default()
+        };
+        assert!(match_trigger(&allowed, &msg, Some(CHANNEL), BOT, false).is_some());
+        assert!(match_trigger(&allowed, &msg, None, BOT, false).is_none());
+
+        let blocked = TriggersConfig {
+            blocked_channels: vec![CHANNEL],
+            ..Default::default()
+        };
+        assert!(match_trigger(&blocked, &msg, Some(CHANNEL), BOT, true).is_none());
+    }
+
+    #[test]
+    fn threads_inherit_parent_overrides_and_assistant_channels() {
+        let msg = message("hi", true, THREAD);
+
+        let assistant = TriggersConfig {
+            assistant_channels: vec![CHANNEL],
+            ..Default::default()
+        };
+        assert!(match_trigger(&assistant, &msg, Some(CHANNEL), BOT, false).is_some());
+
+        let mut overridden = TriggersConfig::default();
+        overridden.overrides.insert(
+            CHANNEL.to_string(),
+            TriggerOverride {
+                mention: Some(false),
+                ..Default::default()
+            },
+        );
+        let mention = message("<@10> hi", true, THREAD);
+        assert!(match_trigger(&overridden, &mention, Some(CHANNEL), BOT, false).is_none());
+        assert!(match_trigger(&overridden, &mention, None, BOT, false).is_some());
+    }
+}
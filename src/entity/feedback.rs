@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+
+/// One finished answer with its usage and the asker's rating, if any.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "feedback")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub rowid: i64,
+    #[sea_orm(unique)]
+    pub turn_id: String,
+    pub message_id: i64,
+    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub user_id: i64,
+    pub model: String,
+    pub total_tokens: i64,
+    /// 1 for 👍, -1 for 👎.
+    pub rating: Option<i32>,
+    pub timestamp_us: i64,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
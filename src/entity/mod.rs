@@ -1,2 +1,4 @@
 pub mod conversations;
+pub mod feedback;
 pub mod important;
+pub mod user_profiles;
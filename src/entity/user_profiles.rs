@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+
+/// Preferences a user stored with `/prefs` or the `set_preference` tool.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "user_profiles")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub rowid: i64,
+    #[sea_orm(unique)]
+    pub user_id: i64,
+    pub language: Option<String>,
+    pub verbosity: Option<String>,
+    pub timezone: Option<String>,
+    pub units: Option<String>,
+    pub instructions: Option<String>,
+    pub updated_us: i64,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
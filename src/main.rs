@@ -1,14 +1,20 @@
 mod agent;
+mod approval;
+mod channels;
 mod config;
 mod discord;
 mod embeddings;
 mod entity;
+mod mcp;
 mod memory;
+mod permissions;
+mod personas;
 mod scheduler;
 mod tools;
 mod vector_db;
 
 use anyhow::Result;
+use std::sync::Arc;
 use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -25,24 +31,77 @@ async fn main() -> Result<()> {
 
     let embedding_service = embeddings::create_embedding_service(config.clone()).await?;
 
-    let vector_db = vector_db::VectorDb::new(&config.storage.data_dir, embedding_service).await?;
+    let vector_db = vector_db::VectorDb::new(
+        &config.storage.data_dir,
+        embedding_service,
+        config.feedback.exclude_downvoted,
+    )
+    .await?;
     let memory_manager = memory::MemoryManager::new(vector_db.clone()).await?;
 
-    let agent = agent::create_agent(config.clone(), memory_manager.clone()).await?;
+    let mcp = mcp::McpManager::start(&config.mcp, &config.reserved_tool_names()).await;
 
-    let scheduler = scheduler::Scheduler::new(&config.storage.data_dir, agent.clone()).await?;
+    let approvals = approval::ApprovalGate::new(&config);
+
+    let thread_parents = Arc::new(channels::ThreadParents::default());
+    let personas = personas::PersonaStore::load(
+        &config.storage.data_dir,
+        config.personas.clone(),
+        thread_parents.clone(),
+    )
+    .await?;
+
+    let agent = agent::create_agent(
+        config.clone(),
+        memory_manager.clone(),
+        mcp,
+        approvals.clone(),
+        personas.clone(),
+    )
+    .await?;
+
+    let scheduler = scheduler::Scheduler::new(
+        &config.storage.data_dir,
+        config.display.clone(),
+        agent.clone(),
+    )
+    .await?;
     agent.set_scheduler(scheduler.clone()).await;
     scheduler.start().await?;
 
-    let discord_bot = discord::Bot::new(config, agent, scheduler.clone()).await?;
+    let discord_bot = discord::Bot::new(
+        config.clone(),
+        agent,
+        scheduler.clone(),
+        approvals,
+        vector_db,
+        personas,
+        thread_parents,
+    )
+    .await?;
+    let shutdown = discord_bot.shutdown_handle();
     let bot_handle = tokio::spawn(async move {
         if let Err(e) = discord_bot.start().await {
             tracing::error!("Discord bot error: {}", e);
         }
     });
 
-    tokio::signal::ctrl_c().await?;
-    info!("Shutdown signal received, saving state...");
+    shutdown_signal().await?;
+    info!(
+        "Shutdown signal received, waiting up to {}s for active replies...",
+        config.shutdown.grace_period
+    );
+
+    let interrupted = shutdown.shutdown().await;
+    if interrupted > 0 {
+        info!(
+            "Interrupted {} replies, stopping sandbox commands",
+            interrupted
+        );
+        if let Err(e) = tools::RunCommand::stop_containers().await {
+            warn!("Failed to stop sandbox containers: {}", e);
+        }
+    }
 
     if let Err(e) = scheduler.shutdown().await {
         warn!("Failed to shutdown scheduler: {}", e);
@@ -53,3 +112,19 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Resolves on Ctrl-C, or on SIGTERM from a service manager.
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
@@ -0,0 +1,355 @@
+mod transport;
+
+use crate::config::{McpConfig, McpServerConfig};
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Duration;
+use tracing::{error, info, warn};
+use transport::{HttpTransport, StdioTransport, Transport};
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+const INIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct McpClient {
+    server: String,
+    transport: Transport,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl McpClient {
+    async fn connect(config: &McpServerConfig) -> Result<Self> {
+        let transport = match (&config.command, &config.url) {
+            (Some(command), _) => Transport::Stdio(StdioTransport::spawn(
+                &config.name,
+                command,
+                &config.args,
+                &config.env,
+            )?),
+            (None, Some(url)) => Transport::Http(HttpTransport::new(url, &config.headers)),
+            (None, None) => bail!("MCP server '{}' needs a command or url", config.name),
+        };
+
+        let client = Self {
+            server: config.name.clone(),
+            transport,
+            next_id: AtomicU64::new(1),
+            timeout: Duration::from_secs(config.timeout),
+        };
+
+        client
+            .request_with_timeout(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": "rustclaw",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+                INIT_TIMEOUT,
+            )
+            .await?;
+        client
+            .transport
+            .notify(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await?;
+
+        Ok(client)
+    }
+
+    async fn request_with_timeout(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let msg = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        self.transport.request(id, msg, timeout).await
+    }
+
+    async fn list_tools(&self) -> Result<Vec<Value>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match cursor {
+                Some(ref c) => json!({"cursor": c}),
+                None => json!({}),
+            };
+            let result = self
+                .request_with_timeout("tools/list", params, INIT_TIMEOUT)
+                .await?;
+            if let Some(page) = result.get("tools").and_then(|t| t.as_array()) {
+                tools.extend(page.iter().cloned());
+            }
+            cursor = result
+                .get("nextCursor")
+                .and_then(|c| c.as_str())
+                .map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Calls a remote tool and flattens its content into text. Returns the
+    /// text and whether the server flagged the result as an error.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<(String, bool)> {
+        let result = self
+            .request_with_timeout(
+                "tools/call",
+                json!({"name": name, "arguments": arguments}),
+                self.timeout,
+            )
+            .await?;
+
+        let mut output = String::new();
+        for item in result
+            .get("content")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            match item.get("type").and_then(|t| t.as_str()) {
+                Some("text") => {
+                    output.push_str(item.get("text").and_then(|t| t.as_str()).unwrap_or(""))
+                }
+                Some(other) => output.push_str(&format!("[{} content omitted]", other)),
+                None => {}
+            }
+        }
+        let is_error = result
+            .get("isError")
+            .and_then(|e| e.as_bool())
+            .unwrap_or(false);
+
+        Ok((output, is_error))
+    }
+}
+
+pub struct McpToolSpec {
+    pub name: String,
+    pub remote_name: String,
+    pub description: String,
+    pub input_schema: Value,
+    pub owner_only: bool,
+    pub client: Arc<McpClient>,
+}
+
+fn sanitize_tool_name(server: &str, tool: &str) -> String {
+    format!("{}_{}", server, tool)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect()
+}
+
+pub struct McpManager {
+    tools: Vec<Arc<McpToolSpec>>,
+}
+
+impl McpManager {
+    /// Connects to the enabled servers and collects their tools. A tool whose
+    /// registered name is in `reserved` or was already taken by another MCP
+    /// tool is skipped.
+    pub async fn start(config: &McpConfig, reserved: &[&str]) -> Arc<Self> {
+        let mut tools = Vec::new();
+        let mut names: HashSet<String> = reserved.iter().map(|n| n.to_string()).collect();
+
+        for server in config.servers.iter().filter(|s| s.enabled) {
+            let client = match McpClient::connect(server).await {
+                Ok(client) => Arc::new(client),
+                Err(e) => {
+                    error!("Failed to connect to MCP server '{}': {}", server.name, e);
+                    continue;
+                }
+            };
+
+            let listed = match client.list_tools().await {
+                Ok(listed) => listed,
+                Err(e) => {
+                    error!(
+                        "Failed to list tools of MCP server '{}': {}",
+                        server.name, e
+                    );
+                    continue;
+                }
+            };
+
+            for tool in listed {
+                let Some(remote_name) = tool.get("name").and_then(|n| n.as_str()) else {
+                    continue;
+                };
+                let name = sanitize_tool_name(&server.name, remote_name);
+                if !names.insert(name.clone()) {
+                    warn!(
+                        "Skipping tool '{}' of MCP server '{}': the name '{}' is already in use",
+                        remote_name, server.name, name
+                    );
+                    continue;
+                }
+                tools.push(Arc::new(McpToolSpec {
+                    name,
+                    remote_name: remote_name.to_string(),
+                    description: tool
+                        .get("description")
+                        .and_then(|d| d.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    input_schema: tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| json!({"type": "object", "properties": {}})),
+                    owner_only: server.owner_only,
+                    client: client.clone(),
+                }));
+            }
+
+            info!(
+                "MCP server '{}' connected ({} tools)",
+                client.server,
+                tools
+                    .iter()
+                    .filter(|t| Arc::ptr_eq(&t.client, &client))
+                    .count()
+            );
+        }
+
+        Arc::new(Self { tools })
+    }
+
+    pub fn tools(&self) -> impl Iterator<Item = &Arc<McpToolSpec>> {
+        self.tools.iter()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::PermissionsConfig;
+    use crate::permissions::{Requester, Role, ToolPermissions};
+
+    fn fixture_server(name: &str, enabled: bool, owner_only: bool) -> McpServerConfig {
+        McpServerConfig {
+            name: name.to_string(),
+            command: Some("python3".to_string()),
+            args: vec![format!(
+                "{}/tests/fixtures/mcp_server.py",
+                env!("CARGO_MANIFEST_DIR")
+            )],
+            env: Default::default(),
+            url: None,
+            headers: Default::default(),
+            enabled,
+            owner_only,
+            timeout: 10,
+        }
+    }
+
+    /// Starts the stdio server in `tests/fixtures/mcp_server.py` as "fixture".
+    pub(crate) async fn start_fixture(enabled: bool, owner_only: bool) -> Arc<McpManager> {
+        McpManager::start(
+            &McpConfig {
+                servers: vec![fixture_server("fixture", enabled, owner_only)],
+            },
+            &crate::tools::BUILTIN_TOOLS,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn discovers_tools_across_pages() {
+        let mcp = start_fixture(true, false).await;
+        let tools: Vec<_> = mcp.tools().collect();
+
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].name, "fixture_echo");
+        assert_eq!(tools[0].remote_name, "echo");
+        assert_eq!(tools[0].description, "Echo the given text");
+        assert_eq!(tools[0].input_schema["required"], json!(["text"]));
+        assert_eq!(tools[1].name, "fixture_fail");
+    }
+
+    #[tokio::test]
+    async fn skips_disabled_servers() {
+        let mcp = start_fixture(false, false).await;
+        assert_eq!(mcp.tools().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn skips_reserved_names() {
+        let mcp = McpManager::start(
+            &McpConfig {
+                servers: vec![fixture_server("fixture", true, false)],
+            },
+            &["fixture_echo"],
+        )
+        .await;
+        let names: Vec<_> = mcp.tools().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["fixture_fail"]);
+    }
+
+    #[tokio::test]
+    async fn skips_names_taken_by_another_server() {
+        let mcp = McpManager::start(
+            &McpConfig {
+                servers: vec![
+                    fixture_server("fixture", true, false),
+                    fixture_server("fixture", true, true),
+                ],
+            },
+            &crate::tools::BUILTIN_TOOLS,
+        )
+        .await;
+        assert_eq!(mcp.tools().count(), 2);
+        assert!(mcp.tools().all(|t| !t.owner_only));
+    }
+
+    #[tokio::test]
+    async fn owner_only_tools_need_an_admin() {
+        let mcp = start_fixture(true, true).await;
+        let user = ToolPermissions::resolve(&PermissionsConfig::default(), &Requester::default());
+        let admin = ToolPermissions::resolve(
+            &PermissionsConfig::default(),
+            &Requester {
+                role: Role::Admin,
+                ..Default::default()
+            },
+        );
+
+        assert!(mcp.tools().all(|t| t.owner_only));
+        assert!(
+            mcp.tools()
+                .all(|t| !user.allows_tool(&t.name, t.owner_only))
+        );
+        assert!(
+            mcp.tools()
+                .all(|t| admin.allows_tool(&t.name, t.owner_only))
+        );
+    }
+
+    #[test]
+    fn sanitizes_tool_names() {
+        assert_eq!(
+            sanitize_tool_name("my server", "get.time"),
+            "my_server_get_time"
+        );
+        assert_eq!(sanitize_tool_name("s", &"x".repeat(100)).len(), 64);
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+use std::{collections::HashMap, process::Stdio, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    sync::{Mutex, oneshot},
+};
+use tracing::{debug, warn};
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+
+fn into_result(msg: &Value) -> Result<Value> {
+    if let Some(err) = msg.get("error") {
+        let message = err
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("unknown error");
+        bail!("MCP error: {}", message);
+    }
+    Ok(msg.get("result").cloned().unwrap_or(Value::Null))
+}
+
+pub(super) enum Transport {
+    Stdio(StdioTransport),
+    Http(HttpTransport),
+}
+
+impl Transport {
+    pub async fn request(&self, id: u64, msg: Value, timeout: Duration) -> Result<Value> {
+        match self {
+            Self::Stdio(t) => t.request(id, msg, timeout).await,
+            Self::Http(t) => t.request(id, msg, timeout).await,
+        }
+    }
+
+    pub async fn notify(&self, msg: Value) -> Result<()> {
+        match self {
+            Self::Stdio(t) => t.send(&msg).await,
+            Self::Http(t) => t.post(&msg).await.map(|_| ()),
+        }
+    }
+}
+
+pub(super) struct StdioTransport {
+    stdin: Arc<Mutex<ChildStdin>>,
+    pending: Pending,
+    _child: Child,
+}
+
+async fn write_line(stdin: &Mutex<ChildStdin>, msg: &Value) -> Result<()> {
+    let mut line = serde_json::to_string(msg)?;
+    line.push('\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+impl StdioTransport {
+    pub fn spawn(
+        server: &str,
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to launch MCP server '{}'", server))?;
+
+        let stdin = Arc::new(Mutex::new(child.stdin.take().context("MCP stdin missing")?));
+        let stdout = child.stdout.take().context("MCP stdout missing")?;
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+
+        let reader_stdin = stdin.clone();
+        let reader_pending = pending.clone();
+        let server = server.to_string();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(msg) = serde_json::from_str::<Value>(&line) else {
+                    debug!("MCP server '{}' wrote non-JSON line: {}", server, line);
+                    continue;
+                };
+
+                // Requests from the server: answer pings, reject everything else.
+                if let Some(method) = msg.get("method").and_then(|m| m.as_str()) {
+                    if let Some(id) = msg.get("id") {
+                        let reply = if method == "ping" {
+                            json!({"jsonrpc": "2.0", "id": id, "result": {}})
+                        } else {
+                            json!({
+                                "jsonrpc": "2.0",
+                                "id": id,
+                                "error": {"code": -32601, "message": "Method not found"}
+                            })
+                        };
+                        if let Err(e) = write_line(&reader_stdin, &reply).await {
+                            warn!("Failed to reply to MCP server '{}': {}", server, e);
+                        }
+                    }
+                    continue;
+                }
+
+                let Some(id) = msg.get("id").and_then(|i| i.as_u64()) else {
+                    continue;
+                };
+                if let Some(tx) = reader_pending.lock().await.remove(&id) {
+                    let _ = tx.send(into_result(&msg));
+                }
+            }
+
+            warn!("MCP server '{}' closed its output", server);
+            for (_, tx) in reader_pending.lock().await.drain() {
+                let _ = tx.send(Err(anyhow!("MCP server '{}' exited", server)));
+            }
+        });
+
+        Ok(Self {
+            stdin,
+            pending,
+            _child: child,
+        })
+    }
+
+    async fn send(&self, msg: &Value) -> Result<()> {
+        write_line(&self.stdin, msg).await
+    }
+
+    async fn request(&self, id: u64, msg: Value, timeout: Duration) -> Result<Value> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+
+        if let Err(e) = self.send(&msg).await {
+            self.pending.lock().await.remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(anyhow!("MCP request dropped")),
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                Err(anyhow!("MCP request timed out"))
+            }
+        }
+    }
+}
+
+pub(super) struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    headers: HashMap<String, String>,
+    session_id: Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str, headers: &HashMap<String, String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            headers: headers.clone(),
+            session_id: Mutex::new(None),
+        }
+    }
+
+    async fn post(&self, msg: &Value) -> Result<String> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(msg);
+        for (key, value) in &self.headers {
+            request = request.header(key, value);
+        }
+        if let Some(ref session_id) = *self.session_id.lock().await {
+            request = request.header("Mcp-Session-Id", session_id);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            bail!("MCP HTTP {}", response.status());
+        }
+
+        if let Some(session_id) = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session_id.lock().await = Some(session_id.to_string());
+        }
+
+        Ok(response.text().await?)
+    }
+
+    async fn request(&self, id: u64, msg: Value, timeout: Duration) -> Result<Value> {
+        let body = tokio::time::timeout(timeout, self.post(&msg))
+            .await
+            .map_err(|_| anyhow!("MCP request timed out"))??;
+
+        // Streamable HTTP servers may answer with a single JSON body or an SSE stream.
+        if let Ok(msg) = serde_json::from_str::<Value>(&body) {
+            return into_result(&msg);
+        }
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+            .find(|msg| msg.get("id").and_then(|i| i.as_u64()) == Some(id))
+            .map(|msg| into_result(&msg))
+            .unwrap_or_else(|| Err(anyhow!("MCP response missing for request {}", id)))
+    }
+}
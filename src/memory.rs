@@ -21,13 +21,25 @@ impl MemoryManager {
         author: &str,
         user_input: &str,
         assistant_response: &str,
-    ) -> Result<()> {
+    ) -> Result<String> {
         self.vector_db
             .add_turn(author, user_input, assistant_response)
             .await
     }
 
-    pub async fn get_context(&self, current_input: &str) -> Result<String> {
+    pub async fn forget_turn(&self, id: &str) -> Result<bool> {
+        self.vector_db.delete_turn(id).await
+    }
+
+    /// Builds the memory section of the prompt. `include_recent` adds the
+    /// latest turns across all conversations. `exclude` leaves out a turn
+    /// that is being replaced.
+    pub async fn get_context(
+        &self,
+        current_input: &str,
+        include_recent: bool,
+        exclude: Option<&str>,
+    ) -> Result<String> {
         let mut context = String::new();
 
         let important = self.vector_db.get_important_context().await?;
@@ -36,8 +48,19 @@ impl MemoryManager {
             context.push('\n');
         }
 
-        let recent = self.vector_db.recent_turns(RECENT_TURN_COUNT).await?;
-        let recent_ids: Vec<String> = recent.iter().map(|t| t.id.clone()).collect();
+        let mut recent = if include_recent {
+            self.vector_db
+                .recent_turns(RECENT_TURN_COUNT + usize::from(exclude.is_some()))
+                .await?
+        } else {
+            Vec::new()
+        };
+        recent.retain(|t| Some(t.id.as_str()) != exclude);
+        if recent.len() > RECENT_TURN_COUNT {
+            recent.remove(0);
+        }
+        let mut skip_ids: Vec<String> = recent.iter().map(|t| t.id.clone()).collect();
+        skip_ids.extend(exclude.map(str::to_string));
 
         if !recent.is_empty() {
             context.push_str("# Recent Conversations\n\n");
@@ -49,7 +72,7 @@ impl MemoryManager {
 
         let semantic = self
             .vector_db
-            .search_turns(current_input, SEMANTIC_SEARCH_COUNT, &recent_ids)
+            .search_turns(current_input, SEMANTIC_SEARCH_COUNT, &skip_ids)
             .await?;
 
         if !semantic.is_empty() {
@@ -0,0 +1,330 @@
+use crate::config::{DiscordConfig, PermissionRule, PermissionsConfig, ToolLimits};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub const OWNER_ONLY_TOOLS: [&str; 4] = [
+    "important_add",
+    "important_delete",
+    "unschedule",
+    "reset_container",
+];
+
+/// Owner-only tools that operators may use as well.
+const OPERATOR_TOOLS: [&str; 1] = ["unschedule"];
+
+const WILDCARD: &str = "*";
+
+/// Access tier of a requester, lowest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    /// Can cancel streams and manage schedules, but not memory.
+    Operator,
+    /// Same rights as the owner.
+    Admin,
+    Owner,
+}
+
+impl Role {
+    /// Resolves a user's tier from `[discord]`. `roles` are the names of the
+    /// user's roles in `guild_id`.
+    pub fn resolve(
+        config: &DiscordConfig,
+        user_id: u64,
+        guild_id: Option<u64>,
+        roles: &[String],
+    ) -> Self {
+        if user_id == config.owner_id {
+            return Role::Owner;
+        }
+        let guild = guild_id.and_then(|id| config.guild_roles.get(&id.to_string()));
+        let has_role = |global: &[String], per_guild: Option<&[String]>| {
+            roles
+                .iter()
+                .any(|r| global.contains(r) || per_guild.is_some_and(|g| g.contains(r)))
+        };
+
+        if config.admin_users.contains(&user_id)
+            || has_role(&config.admin_roles, guild.map(|g| g.admin_roles.as_slice()))
+        {
+            Role::Admin
+        } else if config.operator_users.contains(&user_id)
+            || has_role(
+                &config.operator_roles,
+                guild.map(|g| g.operator_roles.as_slice()),
+            )
+        {
+            Role::Operator
+        } else {
+            Role::User
+        }
+    }
+
+    /// The owner and admins, who have full access.
+    pub fn is_admin(self) -> bool {
+        self >= Role::Admin
+    }
+
+    pub fn is_operator(self) -> bool {
+        self >= Role::Operator
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::User => "Regular User",
+            Role::Operator => "Operator",
+            Role::Admin => "Admin",
+            Role::Owner => "Owner",
+        }
+    }
+}
+
+/// Who is asking and where, as far as the permission policy is concerned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Requester<'a> {
+    pub role: Role,
+    pub user_id: Option<u64>,
+    pub roles: &'a [String],
+    pub guild_id: Option<u64>,
+    pub channel_id: Option<u64>,
+}
+
+impl PermissionRule {
+    fn matches(&self, requester: &Requester<'_>) -> bool {
+        fn any_of<T: PartialEq>(list: &[T], value: Option<T>) -> bool {
+            list.is_empty() || value.is_some_and(|v| list.contains(&v))
+        }
+
+        any_of(&self.users, requester.user_id)
+            && any_of(&self.guilds, requester.guild_id)
+            && any_of(&self.channels, requester.channel_id)
+            && (self.roles.is_empty() || requester.roles.iter().any(|r| self.roles.contains(r)))
+    }
+}
+
+/// The set of tools granted to one request, resolved from `[permissions]`.
+#[derive(Debug, Clone, Default)]
+pub struct ToolPermissions {
+    pub role: Role,
+    base: Option<HashSet<String>>,
+    granted: HashSet<String>,
+    denied: HashSet<String>,
+    /// Upper bound set by a persona; applies to admins as well.
+    only: Option<HashSet<String>>,
+    pub limits: ToolLimits,
+}
+
+impl ToolPermissions {
+    pub fn owner() -> Self {
+        Self {
+            role: Role::Owner,
+            ..Default::default()
+        }
+    }
+
+    pub fn resolve(config: &PermissionsConfig, requester: &Requester<'_>) -> Self {
+        if requester.role.is_admin() {
+            return Self {
+                role: requester.role,
+                ..Default::default()
+            };
+        }
+
+        let mut permissions = Self {
+            role: requester.role,
+            base: config
+                .default_tools
+                .as_ref()
+                .map(|tools| tools.iter().cloned().collect()),
+            granted: HashSet::new(),
+            denied: HashSet::new(),
+            only: None,
+            limits: config.user_limits.clone(),
+        };
+        if requester.role.is_operator() {
+            permissions
+                .granted
+                .extend(OPERATOR_TOOLS.iter().map(|t| t.to_string()));
+        }
+
+        for rule in config.rules.iter().filter(|r| r.matches(requester)) {
+            for tool in &rule.allow {
+                if tool == WILDCARD {
+                    permissions.denied.clear();
+                    permissions.granted.clear();
+                }
+                permissions.denied.remove(tool);
+                permissions.granted.insert(tool.clone());
+            }
+            for tool in &rule.deny {
+                if tool == WILDCARD {
+                    permissions.granted.clear();
+                    permissions.denied.clear();
+                }
+                permissions.granted.remove(tool);
+                permissions.denied.insert(tool.clone());
+            }
+        }
+
+        permissions
+    }
+
+    /// Whether a tool may be used. `owner_only` tools are only available when
+    /// explicitly granted by a rule. Tools named by a rule take precedence over
+    /// a `*` from an earlier rule, since a later `*` clears them.
+    pub fn allows_tool(&self, name: &str, owner_only: bool) -> bool {
+        if self
+            .only
+            .as_ref()
+            .is_some_and(|only| !only.contains(name) && !only.contains(WILDCARD))
+        {
+            return false;
+        }
+        if self.role.is_admin() {
+            return true;
+        }
+        if self.denied.contains(name) {
+            return false;
+        }
+        if self.granted.contains(name) {
+            return true;
+        }
+        if self.denied.contains(WILDCARD) {
+            return false;
+        }
+        if self.granted.contains(WILDCARD) {
+            return true;
+        }
+        !owner_only
+            && self
+                .base
+                .as_ref()
+                .is_none_or(|base| base.contains(name) || base.contains(WILDCARD))
+    }
+
+    /// Narrows the granted tools to `tools`.
+    pub fn restrict_to(&mut self, tools: &[String]) {
+        self.only = Some(tools.iter().cloned().collect());
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        self.allows_tool(name, OWNER_ONLY_TOOLS.contains(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(allow: &[&str], deny: &[&str]) -> PermissionRule {
+        PermissionRule {
+            allow: allow.iter().map(|t| t.to_string()).collect(),
+            deny: deny.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn resolve(rules: Vec<PermissionRule>) -> ToolPermissions {
+        let config = PermissionsConfig {
+            rules,
+            ..Default::default()
+        };
+        ToolPermissions::resolve(&config, &Requester::default())
+    }
+
+    #[test]
+    fn later_rules_win() {
+        let perms = resolve(vec![rule(&[], &["web_search"]), rule(&["web_search"], &[])]);
+        assert!(perms.allows("web_search"));
+
+        let perms = resolve(vec![rule(&["web_search"], &[]), rule(&[], &["web_search"])]);
+        assert!(!perms.allows("web_search"));
+    }
+
+    #[test]
+    fn allow_after_wildcard_deny_is_an_exception() {
+        let perms = resolve(vec![rule(&[], &["*"]), rule(&["web_search"], &[])]);
+        assert!(perms.allows("web_search"));
+        assert!(!perms.allows("run_command"));
+    }
+
+    #[test]
+    fn wildcard_deny_overrides_earlier_allows() {
+        let perms = resolve(vec![rule(&["web_search"], &[]), rule(&[], &["*"])]);
+        assert!(!perms.allows("web_search"));
+    }
+
+    #[test]
+    fn deny_after_wildcard_allow_is_an_exception() {
+        let perms = resolve(vec![rule(&["*"], &[]), rule(&[], &["run_command"])]);
+        assert!(!perms.allows("run_command"));
+        assert!(perms.allows("important_add"));
+    }
+
+    #[test]
+    fn wildcard_allow_overrides_earlier_denies() {
+        let perms = resolve(vec![rule(&[], &["run_command"]), rule(&["*"], &[])]);
+        assert!(perms.allows("run_command"));
+    }
+
+    #[test]
+    fn owner_only_tools_need_a_grant() {
+        let perms = resolve(vec![]);
+        assert!(perms.allows("web_search"));
+        assert!(!perms.allows("important_add"));
+
+        let perms = resolve(vec![rule(&["important_add"], &[])]);
+        assert!(perms.allows("important_add"));
+    }
+
+    #[test]
+    fn default_tools_limit_users_but_not_admins() {
+        let config = PermissionsConfig {
+            default_tools: Some(vec!["weather".to_string()]),
+            ..Default::default()
+        };
+        let user = ToolPermissions::resolve(&config, &Requester::default());
+        let admin = ToolPermissions::resolve(
+            &config,
+            &Requester {
+                role: Role::Admin,
+                ..Default::default()
+            },
+        );
+
+        assert!(user.allows("weather"));
+        assert!(!user.allows("web_search"));
+        assert!(admin.allows("web_search"));
+    }
+
+    #[test]
+    fn rules_only_apply_to_matching_requesters() {
+        let mut scoped = rule(&[], &["run_command"]);
+        scoped.channels = vec![1];
+        let config = PermissionsConfig {
+            rules: vec![scoped],
+            ..Default::default()
+        };
+        let inside = Requester {
+            channel_id: Some(1),
+            ..Default::default()
+        };
+        let outside = Requester {
+            channel_id: Some(2),
+            ..Default::default()
+        };
+
+        assert!(!ToolPermissions::resolve(&config, &inside).allows("run_command"));
+        assert!(ToolPermissions::resolve(&config, &outside).allows("run_command"));
+    }
+
+    #[test]
+    fn persona_restriction_applies_to_admins() {
+        let mut perms = ToolPermissions::owner();
+        perms.restrict_to(&["weather".to_string()]);
+        assert!(perms.allows("weather"));
+        assert!(!perms.allows("web_search"));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::channels::ThreadParents;
+use crate::config::PersonaConfig;
+use anyhow::{Result, bail};
+use serenity::all::Http;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Assignment value that switches a guild or channel back to the default persona.
+pub const DEFAULT_PERSONA: &str = "default";
+
+/// The configured personas and the guild/channel assignments made with
+/// `/persona`, which take precedence over the ones in config.
+pub struct PersonaStore {
+    personas: Vec<PersonaConfig>,
+    assignments: RwLock<HashMap<u64, String>>,
+    parents: Arc<ThreadParents>,
+    data_path: PathBuf,
+}
+
+impl PersonaStore {
+    pub async fn load(
+        data_dir: &Path,
+        personas: Vec<PersonaConfig>,
+        parents: Arc<ThreadParents>,
+    ) -> Result<Arc<Self>> {
+        let mut names = HashSet::new();
+        for persona in &personas {
+            if persona.name == DEFAULT_PERSONA {
+                bail!("persona name '{}' is reserved", DEFAULT_PERSONA);
+            }
+            if !names.insert(persona.name.as_str()) {
+                bail!("persona '{}' is configured more than once", persona.name);
+            }
+        }
+
+        let data_path = data_dir.join("personas.json");
+        let assignments = if data_path.exists() {
+            let content = fs::read_to_string(&data_path).await?;
+            serde_json::from_str(&content)?
+        } else {
+            HashMap::new()
+        };
+        info!(
+            "Loaded {} personas, {} assigned with /persona",
+            personas.len(),
+            assignments.len()
+        );
+        Ok(Arc::new(Self {
+            personas,
+            assignments: RwLock::new(assignments),
+            parents,
+            data_path,
+        }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PersonaConfig> {
+        self.personas.iter().find(|p| p.name == name)
+    }
+
+    pub fn personas(&self) -> &[PersonaConfig] {
+        &self.personas
+    }
+
+    /// The persona for a channel: its assignment, then its parent channel's
+    /// when it is a thread, then its guild's. Runtime assignments win over
+    /// config at each level. Threads are only recognized with `http`.
+    pub async fn resolve(
+        &self,
+        http: Option<&Http>,
+        guild_id: Option<u64>,
+        channel_id: Option<u64>,
+    ) -> Option<&PersonaConfig> {
+        let parent_id = match (http, channel_id) {
+            (Some(http), Some(id)) => self.parents.parent_of(http, id).await,
+            _ => None,
+        };
+        let configured_for = |id: u64| self.personas.iter().find(|p| p.channels.contains(&id));
+
+        let assignments = self.assignments.read().await;
+        let levels = [
+            channel_id.map(|id| (id, configured_for(id))),
+            parent_id.map(|id| (id, configured_for(id))),
+            guild_id.map(|id| (id, self.personas.iter().find(|p| p.guilds.contains(&id)))),
+        ];
+        for (id, configured) in levels.into_iter().flatten() {
+            if let Some(name) = assignments.get(&id) {
+                return self.get(name);
+            }
+            if configured.is_some() {
+                return configured;
+            }
+        }
+        None
+    }
+
+    /// Assigns a persona to a guild or channel, or removes its runtime
+    /// assignment when `name` is `None`. Returns false if there was nothing to remove.
+    pub async fn assign(&self, target: u64, name: Option<&str>) -> Result<bool> {
+        let mut assignments = self.assignments.write().await;
+        let changed = match name {
+            Some(name) => {
+                if name != DEFAULT_PERSONA && self.get(name).is_none() {
+                    bail!("unknown persona '{}'", name);
+                }
+                assignments.insert(target, name.to_string());
+                true
+            }
+            None => assignments.remove(&target).is_some(),
+        };
+        let content = serde_json::to_string_pretty(&*assignments)?;
+        drop(assignments);
+        fs::write(&self.data_path, content).await?;
+        Ok(changed)
+    }
+
+    pub async fn assignments(&self) -> Vec<(u64, String)> {
+        let mut assignments: Vec<_> = self
+            .assignments
+            .read()
+            .await
+            .iter()
+            .map(|(id, name)| (*id, name.clone()))
+            .collect();
+        assignments.sort();
+        assignments
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{agent::Agent, discord};
+use crate::{
+    agent::{Agent, RequestContext},
+    config::DisplayConfig,
+    discord,
+    permissions::Role,
+};
 use anyhow::Result;
 use chrono_tz::Tz;
 use iana_time_zone::get_timezone;
@@ -19,10 +24,31 @@ pub struct ScheduledTask {
     pub cron_expr: String,
     pub prompt: String,
     pub description: String,
+    /// Access tier the task runs with.
     #[serde(default)]
-    pub is_owner: bool,
+    pub role: Role,
+    /// Tasks saved before roles existed; read as `role = "owner"`.
+    #[serde(default, skip_serializing)]
+    is_owner: bool,
     #[serde(default)]
     pub discord_channel_id: Option<u64>,
+    #[serde(default)]
+    pub paused: bool,
+    /// Posts a summary of the channel's last N hours instead of running `prompt`.
+    #[serde(default)]
+    pub summarize_hours: Option<u64>,
+    /// Timezone of the cron expression; the system timezone when unset.
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+impl ScheduledTask {
+    fn tz(&self) -> Result<Tz> {
+        match self.timezone.as_deref() {
+            Some(tz) => Ok(tz.parse()?),
+            None => Ok(get_timezone()?.parse()?),
+        }
+    }
 }
 
 pub struct Scheduler {
@@ -32,6 +58,7 @@ pub struct Scheduler {
     job_ids: Arc<RwLock<HashMap<String, uuid::Uuid>>>,
     data_path: PathBuf,
     discord_http: Arc<RwLock<Option<Arc<Http>>>>,
+    display: Arc<DisplayConfig>,
 }
 
 impl Scheduler {
@@ -44,7 +71,11 @@ impl Scheduler {
         }
     }
 
-    pub async fn new(data_dir: &Path, agent: Arc<dyn Agent>) -> Result<Arc<Self>> {
+    pub async fn new(
+        data_dir: &Path,
+        display: DisplayConfig,
+        agent: Arc<dyn Agent>,
+    ) -> Result<Arc<Self>> {
         let scheduler = JobScheduler::new().await?;
         let data_path = data_dir.join("schedules.json");
 
@@ -55,6 +86,7 @@ impl Scheduler {
             job_ids: Arc::new(RwLock::new(HashMap::new())),
             data_path,
             discord_http: Arc::new(RwLock::new(None)),
+            display: Arc::new(display),
         });
 
         instance.load_tasks().await?;
@@ -64,7 +96,7 @@ impl Scheduler {
 
     pub async fn start(self: &Arc<Self>) -> Result<()> {
         let tasks = self.tasks.read().await;
-        for task in tasks.values() {
+        for task in tasks.values().filter(|t| !t.paused) {
             if let Err(e) = self.register_job(task).await {
                 error!("Failed to register job {}: {}", task.id, e);
             }
@@ -87,22 +119,44 @@ impl Scheduler {
         *self.discord_http.write().await = Some(http);
     }
 
+    pub async fn discord_http(&self) -> Option<Arc<Http>> {
+        self.discord_http.read().await.clone()
+    }
+
     async fn register_job(&self, task: &ScheduledTask) -> Result<()> {
         let agent = self.agent.clone();
         let prompt = task.prompt.clone();
         let task_id = task.id.clone();
-        let is_owner = task.is_owner;
+        let role = task.role;
         let discord_channel_id = task.discord_channel_id;
+        let summarize_hours = task.summarize_hours;
         let discord_http = self.discord_http.clone();
-        let timezone: Tz = get_timezone()?.parse()?;
+        let display = self.display.clone();
+        let timezone = task.tz()?;
 
         let job = Job::new_async_tz(task.cron_expr.as_str(), timezone, move |_uuid, _l| {
             let agent = agent.clone();
             let prompt = prompt.clone();
             let task_id = task_id.clone();
             let discord_http = discord_http.clone();
+            let display = display.clone();
             Box::pin(async move {
                 info!("Running scheduled task: {}", task_id);
+                if let (Some(hours), Some(channel_id)) = (summarize_hours, discord_channel_id) {
+                    let http = { discord_http.read().await.clone() };
+                    if let Some(http) = http
+                        && let Err(e) = discord::post_channel_summary(
+                            http.as_ref(),
+                            agent.as_ref(),
+                            channel_id,
+                            hours,
+                        )
+                        .await
+                    {
+                        error!("Scheduled summary {} failed: {}", task_id, e);
+                    }
+                    return;
+                }
                 let (tx, mut rx) = tokio::sync::mpsc::channel(128);
                 let agent_clone = agent.clone();
                 let prompt_clone = prompt.clone();
@@ -110,10 +164,11 @@ impl Scheduler {
                     agent_clone
                         .process_streaming(
                             &prompt_clone,
-                            is_owner,
-                            discord_channel_id,
-                            None,
-                            &[],
+                            RequestContext {
+                                role,
+                                discord_channel_id,
+                                ..Default::default()
+                            },
                             tx,
                         )
                         .await
@@ -127,8 +182,13 @@ impl Scheduler {
                         if let Some(channel_id) = discord_channel_id {
                             let http = { discord_http.read().await.clone() };
                             if let Some(http) = http {
-                                discord::send_agent_response(http.as_ref(), channel_id, &response)
-                                    .await;
+                                discord::send_agent_response(
+                                    http.as_ref(),
+                                    channel_id,
+                                    &response,
+                                    &display,
+                                )
+                                .await;
                             }
                         }
                     }
@@ -150,23 +210,52 @@ impl Scheduler {
         cron_expr: &str,
         prompt: &str,
         description: &str,
-        is_owner: bool,
+        role: Role,
         discord_channel_id: Option<u64>,
+        timezone: Option<String>,
     ) -> Result<String> {
-        let cron_expr = Self::normalize_cron_expr(cron_expr);
-        let cron_expr = cron_expr.as_str();
-        let timezone: Tz = get_timezone()?.parse()?;
-
-        Job::new_async_tz(cron_expr, timezone, |_, _| Box::pin(async {}))?;
-
         let task = ScheduledTask {
             id: Uuid::new_v4().to_string()[..8].to_string(),
-            cron_expr: cron_expr.to_string(),
+            cron_expr: Self::normalize_cron_expr(cron_expr),
             prompt: prompt.to_string(),
             description: description.to_string(),
-            is_owner,
+            role,
+            is_owner: false,
             discord_channel_id,
+            paused: false,
+            summarize_hours: None,
+            timezone,
+        };
+        self.insert_task(task).await
+    }
+
+    /// Schedules a summary of the channel's last `hours`, posted to it.
+    pub async fn add_summary_task(
+        self: &Arc<Self>,
+        cron_expr: &str,
+        hours: u64,
+        discord_channel_id: u64,
+        timezone: Option<String>,
+    ) -> Result<String> {
+        let task = ScheduledTask {
+            id: Uuid::new_v4().to_string()[..8].to_string(),
+            cron_expr: Self::normalize_cron_expr(cron_expr),
+            prompt: String::new(),
+            description: format!("Summary of the last {}h", hours),
+            role: Role::User,
+            is_owner: false,
+            discord_channel_id: Some(discord_channel_id),
+            paused: false,
+            summarize_hours: Some(hours),
+            timezone,
         };
+        self.insert_task(task).await
+    }
+
+    async fn insert_task(self: &Arc<Self>, task: ScheduledTask) -> Result<String> {
+        Job::new_async_tz(task.cron_expr.as_str(), task.tz()?, |_, _| {
+            Box::pin(async {})
+        })?;
 
         let task_id = task.id.clone();
 
@@ -196,6 +285,36 @@ impl Scheduler {
         }
     }
 
+    /// Pauses or resumes a task without deleting it. Returns false when the
+    /// task does not exist.
+    pub async fn set_paused(&self, task_id: &str, paused: bool) -> Result<bool> {
+        let mut tasks = self.tasks.write().await;
+        let Some(task) = tasks.get_mut(task_id) else {
+            return Ok(false);
+        };
+        if task.paused == paused {
+            return Ok(true);
+        }
+
+        if paused {
+            if let Some(job_id) = self.job_ids.write().await.remove(task_id) {
+                self.scheduler.lock().await.remove(&job_id).await?;
+            }
+        } else {
+            self.register_job(task).await?;
+        }
+        task.paused = paused;
+        drop(tasks);
+        self.save_tasks().await?;
+
+        info!(
+            "{} scheduled task: {}",
+            if paused { "Paused" } else { "Resumed" },
+            task_id
+        );
+        Ok(true)
+    }
+
     pub async fn list_tasks(&self) -> Vec<ScheduledTask> {
         self.tasks.read().await.values().cloned().collect()
     }
@@ -205,7 +324,10 @@ impl Scheduler {
             let content = fs::read_to_string(&self.data_path).await?;
             let tasks: Vec<ScheduledTask> = serde_json::from_str(&content)?;
             let mut task_map = self.tasks.write().await;
-            for task in tasks {
+            for mut task in tasks {
+                if task.is_owner {
+                    task.role = Role::Owner;
+                }
                 task_map.insert(task.id.clone(), task);
             }
             info!("Loaded {} scheduled tasks", task_map.len());
@@ -0,0 +1,62 @@
+use super::error::ToolError;
+use crate::approval::{ApprovalGate, ApprovalRequester, Decision};
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolDyn},
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Wraps another tool so every call waits for an owner decision first.
+pub struct RequiresApproval {
+    pub inner: Box<dyn ToolDyn>,
+    pub gate: Arc<ApprovalGate>,
+    pub requester: ApprovalRequester,
+}
+
+impl Tool for RequiresApproval {
+    const NAME: &'static str = "requires_approval";
+
+    type Error = ToolError;
+    type Args = Value;
+    type Output = Value;
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        let mut definition = self.inner.definition(prompt).await;
+        definition
+            .description
+            .push_str("\nCalls require owner approval and may take a while or be denied.");
+        definition
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let name = self.inner.name();
+        let arguments = args.to_string();
+
+        match self.gate.request(&self.requester, &name, &arguments).await {
+            Decision::Approved { .. } => {}
+            Decision::Denied { .. } => {
+                return Err(ToolError::NotApproved("denied by the owner".into()));
+            }
+            Decision::TimedOut => {
+                return Err(ToolError::NotApproved("approval timed out".into()));
+            }
+            Decision::Unavailable => {
+                return Err(ToolError::NotApproved(
+                    "approval could not be requested".into(),
+                ));
+            }
+        }
+
+        let output = self
+            .inner
+            .call(arguments)
+            .await
+            .map_err(|e| ToolError::Inner(e.to_string()))?;
+        Ok(serde_json::from_str(&output).unwrap_or(Value::String(output)))
+    }
+}
@@ -0,0 +1,191 @@
+use super::error::ToolError;
+use super::run_command::{RunCommand, RunCommandArgs};
+use crate::config::{CustomHttpConfig, CustomToolConfig};
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn shell_quote(value: &Value) -> String {
+    format!("'{}'", value_to_text(value).replace('\'', "'\\''"))
+}
+
+fn percent_encode(value: &Value) -> String {
+    let mut out = String::new();
+    for byte in value_to_text(value).bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+fn header_value(value: &Value) -> String {
+    value_to_text(value).replace(['\r', '\n'], " ")
+}
+
+fn json_value(value: &Value) -> String {
+    value.to_string()
+}
+
+/// Replaces `{{name}}` placeholders with the encoded argument value. Missing
+/// arguments are treated as null.
+fn render_template(
+    template: &str,
+    args: &Map<String, Value>,
+    encode: fn(&Value) -> String,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let key = after[..end].trim();
+        out.push_str(&encode(args.get(key).unwrap_or(&Value::Null)));
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Reads at most `cap` bytes of a response body.
+async fn read_capped(mut response: reqwest::Response, cap: usize) -> Result<String, ToolError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| ToolError::FetchFailed(e.to_string()))?
+    {
+        body.extend_from_slice(&chunk[..chunk.len().min(cap - body.len())]);
+        if body.len() >= cap {
+            break;
+        }
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+#[derive(Clone)]
+pub struct CustomTool {
+    pub def: CustomToolConfig,
+    /// Runs `command` tools, with the requester's sandbox limits.
+    pub sandbox: RunCommand,
+    pub client: reqwest::Client,
+}
+
+impl CustomTool {
+    async fn call_http(
+        &self,
+        http: &CustomHttpConfig,
+        args: &Map<String, Value>,
+    ) -> Result<String, ToolError> {
+        let method = reqwest::Method::from_bytes(http.method.to_uppercase().as_bytes())
+            .map_err(|e| ToolError::FetchFailed(format!("Invalid HTTP method: {}", e)))?;
+        let url = render_template(&http.url, args, percent_encode);
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .timeout(Duration::from_secs(self.def.timeout));
+        for (key, value) in &http.headers {
+            request = request.header(key, render_template(value, args, header_value));
+        }
+        if let Some(ref body) = http.body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(render_template(body, args, json_value));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ToolError::FetchFailed(e.to_string()))?;
+        let status = response.status();
+        // Enough bytes for max_output characters of any width.
+        let text = read_capped(response, self.def.max_output.saturating_mul(4)).await?;
+
+        if !status.is_success() {
+            return Err(ToolError::FetchFailed(format!(
+                "HTTP {}: {}",
+                status,
+                self.truncate(text)
+            )));
+        }
+        Ok(text)
+    }
+
+    async fn call_command(
+        &self,
+        command: &str,
+        args: &Map<String, Value>,
+    ) -> Result<String, ToolError> {
+        self.sandbox
+            .call(RunCommandArgs {
+                command: render_template(command, args, shell_quote),
+            })
+            .await
+    }
+
+    fn truncate(&self, text: String) -> String {
+        if text.chars().count() <= self.def.max_output {
+            return text;
+        }
+        let truncated: String = text.chars().take(self.def.max_output).collect();
+        format!(
+            "{}\n… (output truncated to {} characters)",
+            truncated, self.def.max_output
+        )
+    }
+}
+
+impl Tool for CustomTool {
+    const NAME: &'static str = "custom";
+
+    type Error = ToolError;
+    type Args = Value;
+    type Output = String;
+
+    fn name(&self) -> String {
+        self.def.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: self.def.name.clone(),
+            description: self.def.description.clone(),
+            parameters: self.def.parameters.clone(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let args = match args {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+
+        let output = if let Some(ref http) = self.def.http {
+            self.call_http(http, &args).await?
+        } else if let Some(ref command) = self.def.command {
+            self.call_command(command, &args).await?
+        } else {
+            return Err(ToolError::CommandFailed(format!(
+                "Custom tool '{}' has neither http nor command configured",
+                self.def.name
+            )));
+        };
+
+        Ok(self.truncate(output))
+    }
+}
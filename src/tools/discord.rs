@@ -0,0 +1,615 @@
+use super::error::ToolError;
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serenity::all::{
+    AutoArchiveDuration, Channel, ChannelId, ChannelType, CreateAllowedMentions, CreateMessage,
+    CreatePoll, CreatePollAnswer, CreateThread, GetMessages, GuildChannel, Http, Message,
+    MessageId, Permissions, ReactionType, UserId,
+};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_HISTORY: u64 = 30;
+const MAX_HISTORY: u64 = 100;
+const MAX_HISTORY_CHARS: usize = 500;
+const MAX_THREAD_NAME: usize = 100;
+const MAX_POLL_ANSWERS: usize = 10;
+const DEFAULT_POLL_HOURS: u64 = 24;
+const MAX_POLL_HOURS: u64 = 768;
+const MAX_MESSAGE_LEN: usize = 2000;
+
+fn failed(message: impl Into<String>) -> ToolError {
+    ToolError::DiscordFailed(message.into())
+}
+
+fn parse_id(value: &str, field: &str) -> Result<u64, ToolError> {
+    value
+        .trim()
+        .trim_start_matches("<#")
+        .trim_end_matches('>')
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .ok_or_else(|| failed(format!("Invalid {}: {}", field, value)))
+}
+
+/// The bot's Discord client plus who the agent is acting for. Every Discord
+/// tool checks the caller's permissions in the target channel first.
+#[derive(Clone)]
+pub struct DiscordAccess {
+    pub http: Arc<Http>,
+    /// Channel the request came from; the default target.
+    pub channel_id: Option<u64>,
+    pub user_id: Option<u64>,
+}
+
+impl DiscordAccess {
+    /// Resolves the target channel and checks that the caller holds
+    /// `required` there. Without a known caller (e.g. scheduled tasks) and in
+    /// DMs, only the current channel is allowed.
+    async fn authorize(
+        &self,
+        channel: Option<&str>,
+        required: Permissions,
+    ) -> Result<ChannelId, ToolError> {
+        let channel_id = match channel {
+            Some(id) => parse_id(id, "channel_id")?,
+            None => self
+                .channel_id
+                .ok_or_else(|| failed("No current channel; pass channel_id"))?,
+        };
+        let channel_id = ChannelId::new(channel_id);
+        let is_current = self.channel_id == Some(channel_id.get());
+
+        let channel = channel_id
+            .to_channel(&self.http)
+            .await
+            .map_err(|e| failed(format!("Unknown channel {}: {}", channel_id, e)))?;
+        let (Channel::Guild(channel), Some(user_id)) = (channel, self.user_id) else {
+            return if is_current {
+                Ok(channel_id)
+            } else {
+                Err(failed(
+                    "Permission denied: only the current channel is allowed",
+                ))
+            };
+        };
+
+        let user_id = UserId::new(user_id);
+        let granted = self.permissions_in(&channel, user_id).await?;
+        let mut required = required;
+        if channel.thread_metadata.is_some() {
+            if required.contains(Permissions::SEND_MESSAGES) {
+                required.remove(Permissions::SEND_MESSAGES);
+                required.insert(Permissions::SEND_MESSAGES_IN_THREADS);
+            }
+            if channel.kind == ChannelType::PrivateThread
+                && !granted.contains(Permissions::MANAGE_THREADS)
+                && channel_id
+                    .get_thread_member(&self.http, user_id, false)
+                    .await
+                    .is_err()
+            {
+                return Err(failed(format!(
+                    "Permission denied: the user isn't a member of the private thread <#{}>",
+                    channel_id
+                )));
+            }
+        }
+        if granted.contains(required) {
+            Ok(channel_id)
+        } else {
+            Err(failed(format!(
+                "Permission denied: the user lacks {} in <#{}>",
+                required
+                    .difference(granted)
+                    .get_permission_names()
+                    .join(", "),
+                channel_id
+            )))
+        }
+    }
+
+    /// A member's permissions in a channel; threads use their parent's, so
+    /// `authorize` checks thread membership and thread permissions itself.
+    async fn permissions_in(
+        &self,
+        channel: &GuildChannel,
+        user_id: UserId,
+    ) -> Result<Permissions, ToolError> {
+        let parent;
+        let channel = match channel.parent_id {
+            Some(parent_id) if channel.thread_metadata.is_some() => {
+                parent = parent_id
+                    .to_channel(&self.http)
+                    .await
+                    .ok()
+                    .and_then(Channel::guild)
+                    .ok_or_else(|| failed("Couldn't read the thread's parent channel"))?;
+                &parent
+            }
+            _ => channel,
+        };
+        let guild = channel
+            .guild_id
+            .to_partial_guild(&self.http)
+            .await
+            .map_err(|e| failed(format!("Couldn't read the server: {}", e)))?;
+        let member = guild
+            .member(&self.http, user_id)
+            .await
+            .map_err(|_| failed("Permission denied: the user isn't a member of that server"))?;
+        Ok(guild.user_permissions_in(channel, &member))
+    }
+}
+
+fn format_message(msg: &Message) -> String {
+    let author = msg
+        .author
+        .global_name
+        .as_deref()
+        .unwrap_or(&msg.author.name);
+    let time = chrono::DateTime::from_timestamp(msg.timestamp.unix_timestamp(), 0)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+    let mut content: String = msg.content.chars().take(MAX_HISTORY_CHARS).collect();
+    if msg.content.chars().count() > MAX_HISTORY_CHARS {
+        content.push('…');
+    }
+    if !msg.attachments.is_empty() {
+        let _ = write!(content, " [{} attachment(s)]", msg.attachments.len());
+    }
+    format!(
+        "[{}] {} {}: {}",
+        msg.id,
+        time,
+        author,
+        content.replace('\n', " ")
+    )
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ReadChannelHistoryArgs {
+    pub channel_id: Option<String>,
+    pub limit: Option<u64>,
+    pub before: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct ReadChannelHistory {
+    pub discord: DiscordAccess,
+}
+
+impl Tool for ReadChannelHistory {
+    const NAME: &'static str = "read_channel_history";
+
+    type Error = ToolError;
+    type Args = ReadChannelHistoryArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Read recent messages in a Discord channel, oldest first. \
+                          Each line starts with the message ID."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "channel_id": {
+                        "type": "string",
+                        "description": "Channel ID (default: the current channel)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Number of messages (default: 30, max: 100)"
+                    },
+                    "before": {
+                        "type": "string",
+                        "description": "Only messages before this message ID, for paging back"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let channel_id = self
+            .discord
+            .authorize(
+                args.channel_id.as_deref(),
+                Permissions::VIEW_CHANNEL | Permissions::READ_MESSAGE_HISTORY,
+            )
+            .await?;
+
+        let limit = args.limit.unwrap_or(DEFAULT_HISTORY).clamp(1, MAX_HISTORY);
+        let mut request = GetMessages::new().limit(limit as u8);
+        if let Some(before) = &args.before {
+            request = request.before(MessageId::new(parse_id(before, "before")?));
+        }
+        let messages = channel_id
+            .messages(&self.discord.http, request)
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+
+        if messages.is_empty() {
+            return Ok("No messages".to_string());
+        }
+        let lines: Vec<String> = messages.iter().rev().map(format_message).collect();
+        Ok(lines.join("\n"))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AddReactionArgs {
+    pub channel_id: Option<String>,
+    pub message_id: String,
+    pub emoji: String,
+}
+
+#[derive(Clone)]
+pub struct AddReaction {
+    pub discord: DiscordAccess,
+}
+
+impl Tool for AddReaction {
+    const NAME: &'static str = "add_reaction";
+
+    type Error = ToolError;
+    type Args = AddReactionArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "React to a Discord message with an emoji".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "channel_id": {
+                        "type": "string",
+                        "description": "Channel ID (default: the current channel)"
+                    },
+                    "message_id": {
+                        "type": "string",
+                        "description": "ID of the message to react to"
+                    },
+                    "emoji": {
+                        "type": "string",
+                        "description": "Unicode emoji (e.g., '👍') or custom emoji ('<:name:id>')"
+                    }
+                },
+                "required": ["message_id", "emoji"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let channel_id = self
+            .discord
+            .authorize(
+                args.channel_id.as_deref(),
+                Permissions::VIEW_CHANNEL
+                    | Permissions::READ_MESSAGE_HISTORY
+                    | Permissions::ADD_REACTIONS,
+            )
+            .await?;
+        let message_id = MessageId::new(parse_id(&args.message_id, "message_id")?);
+        let reaction = ReactionType::try_from(args.emoji.trim())
+            .map_err(|_| failed(format!("Invalid emoji: {}", args.emoji)))?;
+
+        channel_id
+            .create_reaction(&self.discord.http, message_id, reaction)
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        Ok(format!("Reacted with {}", args.emoji.trim()))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateThreadArgs {
+    pub channel_id: Option<String>,
+    pub message_id: Option<String>,
+    pub name: String,
+}
+
+#[derive(Clone)]
+pub struct CreateThreadTool {
+    pub discord: DiscordAccess,
+}
+
+impl Tool for CreateThreadTool {
+    const NAME: &'static str = "create_thread";
+
+    type Error = ToolError;
+    type Args = CreateThreadArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Create a public Discord thread, optionally on an existing message"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "channel_id": {
+                        "type": "string",
+                        "description": "Channel ID (default: the current channel)"
+                    },
+                    "message_id": {
+                        "type": "string",
+                        "description": "Message to start the thread on (optional)"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Thread name (max 100 characters)"
+                    }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let channel_id = self
+            .discord
+            .authorize(
+                args.channel_id.as_deref(),
+                Permissions::VIEW_CHANNEL | Permissions::CREATE_PUBLIC_THREADS,
+            )
+            .await?;
+        let name: String = args.name.trim().chars().take(MAX_THREAD_NAME).collect();
+        if name.is_empty() {
+            return Err(failed("Thread name is empty"));
+        }
+        let builder = CreateThread::new(name).auto_archive_duration(AutoArchiveDuration::OneDay);
+
+        let thread = match &args.message_id {
+            Some(message_id) => {
+                let message_id = MessageId::new(parse_id(message_id, "message_id")?);
+                channel_id
+                    .create_thread_from_message(&self.discord.http, message_id, builder)
+                    .await
+            }
+            None => {
+                channel_id
+                    .create_thread(&self.discord.http, builder.kind(ChannelType::PublicThread))
+                    .await
+            }
+        }
+        .map_err(|e| failed(e.to_string()))?;
+
+        Ok(format!(
+            "Created thread <#{}> (ID: {})",
+            thread.id, thread.id
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreatePollArgs {
+    pub channel_id: Option<String>,
+    pub question: String,
+    pub answers: Vec<String>,
+    pub duration_hours: Option<u64>,
+    #[serde(default)]
+    pub allow_multiselect: bool,
+}
+
+#[derive(Clone)]
+pub struct CreatePollTool {
+    pub discord: DiscordAccess,
+}
+
+impl Tool for CreatePollTool {
+    const NAME: &'static str = "create_poll";
+
+    type Error = ToolError;
+    type Args = CreatePollArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Start a Discord poll".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "channel_id": {
+                        "type": "string",
+                        "description": "Channel ID (default: the current channel)"
+                    },
+                    "question": {
+                        "type": "string",
+                        "description": "The poll question"
+                    },
+                    "answers": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Between 2 and 10 answers"
+                    },
+                    "duration_hours": {
+                        "type": "integer",
+                        "description": "How long the poll runs (default: 24, max: 768)"
+                    },
+                    "allow_multiselect": {
+                        "type": "boolean",
+                        "description": "Allow voting for several answers (default: false)"
+                    }
+                },
+                "required": ["question", "answers"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        if !(2..=MAX_POLL_ANSWERS).contains(&args.answers.len()) {
+            return Err(failed("A poll needs between 2 and 10 answers"));
+        }
+        let channel_id = self
+            .discord
+            .authorize(
+                args.channel_id.as_deref(),
+                Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::SEND_POLLS,
+            )
+            .await?;
+
+        let hours = args
+            .duration_hours
+            .unwrap_or(DEFAULT_POLL_HOURS)
+            .clamp(1, MAX_POLL_HOURS);
+        let answers = args
+            .answers
+            .iter()
+            .map(|answer| CreatePollAnswer::new().text(answer.trim()))
+            .collect();
+        let mut poll = CreatePoll::new()
+            .question(args.question.trim())
+            .answers(answers)
+            .duration(Duration::from_secs(hours * 3600));
+        if args.allow_multiselect {
+            poll = poll.allow_multiselect();
+        }
+
+        let message = channel_id
+            .send_message(&self.discord.http, CreateMessage::new().poll(poll))
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        Ok(format!(
+            "Poll started in <#{}> for {}h (message ID: {})",
+            channel_id, hours, message.id
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PinMessageArgs {
+    pub channel_id: Option<String>,
+    pub message_id: String,
+}
+
+#[derive(Clone)]
+pub struct PinMessage {
+    pub discord: DiscordAccess,
+}
+
+impl Tool for PinMessage {
+    const NAME: &'static str = "pin_message";
+
+    type Error = ToolError;
+    type Args = PinMessageArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Pin a Discord message".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "channel_id": {
+                        "type": "string",
+                        "description": "Channel ID (default: the current channel)"
+                    },
+                    "message_id": {
+                        "type": "string",
+                        "description": "ID of the message to pin"
+                    }
+                },
+                "required": ["message_id"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let channel_id = self
+            .discord
+            .authorize(
+                args.channel_id.as_deref(),
+                Permissions::VIEW_CHANNEL | Permissions::MANAGE_MESSAGES,
+            )
+            .await?;
+        let message_id = MessageId::new(parse_id(&args.message_id, "message_id")?);
+
+        channel_id
+            .pin(&self.discord.http, message_id)
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        Ok(format!("Pinned message {}", message_id))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SendToChannelArgs {
+    pub channel_id: String,
+    pub content: String,
+}
+
+#[derive(Clone)]
+pub struct SendToChannel {
+    pub discord: DiscordAccess,
+}
+
+impl Tool for SendToChannel {
+    const NAME: &'static str = "send_to_channel";
+
+    type Error = ToolError;
+    type Args = SendToChannelArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Post a message in another Discord channel. Mentions don't ping."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "channel_id": {
+                        "type": "string",
+                        "description": "Channel ID to post in"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "Message text (max 2000 characters)"
+                    }
+                },
+                "required": ["channel_id", "content"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        if args.content.trim().is_empty() {
+            return Err(failed("Message is empty"));
+        }
+        if args.content.chars().count() > MAX_MESSAGE_LEN {
+            return Err(failed("Message is longer than 2000 characters"));
+        }
+        let channel_id = self
+            .discord
+            .authorize(
+                Some(&args.channel_id),
+                Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+            )
+            .await?;
+
+        let message = channel_id
+            .send_message(
+                &self.discord.http,
+                CreateMessage::new()
+                    .content(args.content)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        Ok(format!(
+            "Sent to <#{}> (message ID: {})",
+            channel_id, message.id
+        ))
+    }
+}
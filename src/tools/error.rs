@@ -12,8 +12,18 @@ pub enum ToolError {
     FetchFailed(String),
     #[error("Schedule operation failed: {0}")]
     ScheduleFailed(String),
+    #[error("Preference not saved: {0}")]
+    PreferenceFailed(String),
     #[error("Weather operation failed: {0}")]
     WeatherFailed(String),
+    #[error("Discord operation failed: {0}")]
+    DiscordFailed(String),
+    #[error("MCP tool failed: {0}")]
+    McpFailed(String),
+    #[error("Tool call not approved: {0}")]
+    NotApproved(String),
+    #[error("{0}")]
+    Inner(String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Timeout")]
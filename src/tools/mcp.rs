@@ -0,0 +1,76 @@
+use super::error::ToolError;
+use crate::mcp::McpToolSpec;
+use rig::{completion::ToolDefinition, tool::Tool};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct McpTool {
+    pub spec: Arc<McpToolSpec>,
+}
+
+impl Tool for McpTool {
+    const NAME: &'static str = "mcp";
+
+    type Error = ToolError;
+    type Args = serde_json::Value;
+    type Output = String;
+
+    fn name(&self) -> String {
+        self.spec.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: self.spec.name.clone(),
+            description: self.spec.description.clone(),
+            parameters: self.spec.input_schema.clone(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (output, is_error) = self
+            .spec
+            .client
+            .call_tool(&self.spec.remote_name, args)
+            .await
+            .map_err(|e| ToolError::McpFailed(e.to_string()))?;
+
+        if is_error {
+            return Err(ToolError::McpFailed(output));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::tests::start_fixture;
+    use serde_json::json;
+
+    async fn fixture_tool(name: &str) -> McpTool {
+        let mcp = start_fixture(true, false).await;
+        let spec = mcp
+            .tools()
+            .find(|t| t.remote_name == name)
+            .expect("fixture tool")
+            .clone();
+        McpTool { spec }
+    }
+
+    #[tokio::test]
+    async fn calls_remote_tool() {
+        let tool = fixture_tool("echo").await;
+        assert_eq!(tool.name(), "fixture_echo");
+
+        let output = tool.call(json!({"text": "hello"})).await.unwrap();
+        assert_eq!(output, "hello");
+    }
+
+    #[tokio::test]
+    async fn reports_tool_errors() {
+        let tool = fixture_tool("fail").await;
+        let err = tool.call(json!({})).await.unwrap_err();
+        assert!(matches!(err, ToolError::McpFailed(ref msg) if msg == "boom"));
+    }
+}
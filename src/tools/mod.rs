@@ -1,6 +1,12 @@
+mod approval;
+mod custom;
+mod discord;
 mod error;
 mod get_transcript;
 mod important;
+mod mcp;
+mod outcome;
+mod preferences;
 mod run_command;
 mod schedule;
 mod search_memory;
@@ -12,8 +18,17 @@ mod web_fetch;
 mod web_news;
 mod web_search;
 
+pub use approval::RequiresApproval;
+pub use custom::CustomTool;
+pub use discord::{
+    AddReaction, CreatePollTool, CreateThreadTool, DiscordAccess, PinMessage, ReadChannelHistory,
+    SendToChannel,
+};
 pub use get_transcript::GetTranscript;
 pub use important::{ImportantAdd, ImportantDelete, ImportantList};
+pub use mcp::McpTool;
+pub use outcome::{ToolOutcomes, TracksOutcome};
+pub use preferences::SetPreference;
 pub use run_command::{ResetContainer, RunCommand};
 pub use schedule::{ScheduleAdd, ScheduleDelete, ScheduleList};
 pub use search_memory::SearchMemory;
@@ -24,3 +39,33 @@ pub use weather::Weather;
 pub use web_fetch::WebFetch;
 pub use web_news::WebNews;
 pub use web_search::WebSearch;
+
+use rig::tool::Tool;
+
+/// Names of the tools built into the bot, which MCP and custom tools may not reuse.
+pub const BUILTIN_TOOLS: [&str; 24] = [
+    RunCommand::NAME,
+    ResetContainer::NAME,
+    SendFile::NAME,
+    TypstRender::NAME,
+    SearchMemory::NAME,
+    ImportantList::NAME,
+    ImportantAdd::NAME,
+    ImportantDelete::NAME,
+    WebSearch::NAME,
+    WebNews::NAME,
+    WebFetch::NAME,
+    Weather::NAME,
+    SearchYouTube::NAME,
+    GetTranscript::NAME,
+    SetPreference::NAME,
+    ScheduleAdd::NAME,
+    ScheduleList::NAME,
+    ScheduleDelete::NAME,
+    ReadChannelHistory::NAME,
+    AddReaction::NAME,
+    CreateThreadTool::NAME,
+    CreatePollTool::NAME,
+    PinMessage::NAME,
+    SendToChannel::NAME,
+];
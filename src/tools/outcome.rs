@@ -0,0 +1,105 @@
+use super::error::ToolError;
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolDyn},
+};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+/// A tool call as the tool sees it: its name and arguments. Tools aren't
+/// given the call id, so this is what ties an outcome back to the call.
+type CallKey = (String, String);
+
+/// Whether each finished tool call succeeded. Calls with the same name and
+/// arguments are queued in the order they finished.
+#[derive(Clone, Default)]
+pub struct ToolOutcomes {
+    outcomes: Arc<Mutex<HashMap<CallKey, VecDeque<bool>>>>,
+    /// Name and arguments of each call id seen in the stream.
+    calls: Arc<Mutex<HashMap<String, CallKey>>>,
+}
+
+impl ToolOutcomes {
+    fn record(&self, name: &str, args: &Value, success: bool) {
+        if let Ok(mut outcomes) = self.outcomes.lock() {
+            outcomes
+                .entry((name.to_string(), args.to_string()))
+                .or_default()
+                .push_back(success);
+        }
+    }
+
+    /// Remembers the tool call with id `id` so its outcome can be found later.
+    pub fn start(&self, id: &str, name: &str, args: &Value) {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.insert(id.to_string(), (name.to_string(), args.to_string()));
+        }
+    }
+
+    /// The outcome of the tool call with id `id`.
+    pub fn take(&self, id: &str) -> Option<bool> {
+        let key = self.calls.lock().ok()?.remove(id)?;
+        self.outcomes.lock().ok()?.get_mut(&key)?.pop_front()
+    }
+}
+
+/// Wraps another tool to record whether its calls returned an error.
+pub struct TracksOutcome {
+    pub inner: Box<dyn ToolDyn>,
+    pub outcomes: ToolOutcomes,
+}
+
+impl Tool for TracksOutcome {
+    const NAME: &'static str = "tracks_outcome";
+
+    type Error = ToolError;
+    type Args = Value;
+    type Output = Value;
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.inner.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.inner.call(args.to_string()).await;
+        self.outcomes
+            .record(&self.inner.name(), &args, result.is_ok());
+
+        let output = result.map_err(|e| ToolError::Inner(e.to_string()))?;
+        Ok(serde_json::from_str(&output).unwrap_or(Value::String(output)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn outcomes_follow_the_call_id() {
+        let outcomes = ToolOutcomes::default();
+        outcomes.start("a", "web_fetch", &json!({"url": "https://a"}));
+        outcomes.start("b", "web_fetch", &json!({"url": "https://b"}));
+
+        outcomes.record("web_fetch", &json!({"url": "https://b"}), true);
+        outcomes.record("web_fetch", &json!({"url": "https://a"}), false);
+
+        assert_eq!(outcomes.take("a"), Some(false));
+        assert_eq!(outcomes.take("b"), Some(true));
+        assert_eq!(outcomes.take("a"), None);
+    }
+
+    #[test]
+    fn unknown_calls_have_no_outcome() {
+        let outcomes = ToolOutcomes::default();
+        outcomes.record("weather", &json!({}), true);
+        assert_eq!(outcomes.take("missing"), None);
+    }
+}
@@ -0,0 +1,75 @@
+use super::error::ToolError;
+use crate::agent::Preference;
+use crate::vector_db::VectorDb;
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+
+#[derive(Deserialize, Serialize)]
+pub struct SetPreferenceArgs {
+    pub preference: Preference,
+    #[serde(default)]
+    pub value: String,
+}
+
+#[derive(Clone)]
+pub struct SetPreference {
+    pub vectordb: Arc<VectorDb>,
+    pub user_id: u64,
+}
+
+impl Tool for SetPreference {
+    const NAME: &'static str = "set_preference";
+
+    type Error = ToolError;
+    type Args = SetPreferenceArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Save a preference of the current user for future answers. Use when \
+                          the user states how they want to be answered, e.g. their language or \
+                          timezone. An empty value clears the preference."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "preference": {
+                        "type": "string",
+                        "enum": ["language", "verbosity", "timezone", "units", "instructions"],
+                        "description": "verbosity: brief, normal or detailed. timezone: IANA name like Europe/Berlin. units: metric or imperial. instructions: free-form custom instructions."
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "The new value, or empty to clear it"
+                    }
+                },
+                "required": ["preference", "value"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let mut preferences = self
+            .vectordb
+            .user_preferences(self.user_id)
+            .await
+            .map_err(|e| ToolError::MemoryFailed(e.to_string()))?;
+        preferences
+            .set(args.preference, &args.value)
+            .map_err(|e| ToolError::PreferenceFailed(e.to_string()))?;
+        let name = args.preference.name();
+        let reply = match preferences.get(args.preference) {
+            Some(value) => format!("Saved {}: {}", name, value),
+            None => format!("Cleared {}", name),
+        };
+        self.vectordb
+            .save_user_preferences(self.user_id, preferences)
+            .await
+            .map_err(|e| ToolError::MemoryFailed(e.to_string()))?;
+
+        Ok(reply)
+    }
+}
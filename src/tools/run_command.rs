@@ -21,6 +21,7 @@ use tracing::{debug, info, warn};
 
 const SANDBOX_IMAGE: &str = "nikolaik/python-nodejs:latest";
 const CONTAINER_NAME: &str = "rustclaw-sandbox";
+const OFFLINE_CONTAINER_NAME: &str = "rustclaw-sandbox-offline";
 
 static CONTAINER_LOCK: std::sync::LazyLock<Mutex<()>> = std::sync::LazyLock::new(|| Mutex::new(()));
 
@@ -32,14 +33,32 @@ pub struct RunCommandArgs {
 #[derive(Clone)]
 pub struct RunCommand {
     pub config: Arc<Config>,
+    pub offline: bool,
+    pub timeout: Option<u64>,
 }
 
 impl RunCommand {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            offline: false,
+            timeout: None,
+        }
+    }
+
     pub fn workspace_path(config: &Config) -> PathBuf {
         config.storage.data_dir.join("workspace")
     }
 
-    async fn create_container(docker: &Docker) -> Result<(), ToolError> {
+    fn container_name(&self) -> &'static str {
+        if self.offline {
+            OFFLINE_CONTAINER_NAME
+        } else {
+            CONTAINER_NAME
+        }
+    }
+
+    async fn create_container(docker: &Docker, name: &str, offline: bool) -> Result<(), ToolError> {
         let volume_name = "rustclaw-workspace";
         let bind = format!("{}:/workspace", volume_name);
 
@@ -52,15 +71,13 @@ impl RunCommand {
             image: Some(SANDBOX_IMAGE.to_string()),
             cmd: Some(vec!["sleep".to_string(), "infinity".to_string()]),
             working_dir: Some("/workspace".to_string()),
-            network_disabled: Some(false),
+            network_disabled: Some(offline),
             host_config: Some(host_config),
             tty: Some(true),
             ..Default::default()
         };
 
-        let options = CreateContainerOptionsBuilder::new()
-            .name(CONTAINER_NAME)
-            .build();
+        let options = CreateContainerOptionsBuilder::new().name(name).build();
 
         docker
             .create_container(Some(options), container_config)
@@ -68,11 +85,11 @@ impl RunCommand {
             .map_err(|e| ToolError::CommandFailed(format!("Container creation failed: {}", e)))?;
 
         docker
-            .start_container(CONTAINER_NAME, None)
+            .start_container(name, None)
             .await
             .map_err(|e| ToolError::CommandFailed(format!("Container start failed: {}", e)))?;
 
-        info!("Persistent sandbox container started");
+        info!("Persistent sandbox container started: {}", name);
         Ok(())
     }
 
@@ -94,10 +111,10 @@ impl RunCommand {
         Ok(())
     }
 
-    async fn ensure_container(docker: &Docker) -> Result<(), ToolError> {
+    async fn ensure_container(docker: &Docker, name: &str, offline: bool) -> Result<(), ToolError> {
         let _lock = CONTAINER_LOCK.lock().await;
 
-        match docker.inspect_container(CONTAINER_NAME, None).await {
+        match docker.inspect_container(name, None).await {
             Ok(info) => {
                 let current_image = info
                     .config
@@ -112,16 +129,13 @@ impl RunCommand {
                     );
 
                     docker
-                        .stop_container(
-                            CONTAINER_NAME,
-                            Some(StopContainerOptionsBuilder::new().t(2).build()),
-                        )
+                        .stop_container(name, Some(StopContainerOptionsBuilder::new().t(2).build()))
                         .await
                         .ok();
 
                     docker
                         .remove_container(
-                            CONTAINER_NAME,
+                            name,
                             Some(RemoveContainerOptionsBuilder::new().force(true).build()),
                         )
                         .await
@@ -130,26 +144,23 @@ impl RunCommand {
                         })?;
 
                     Self::ensure_image(docker).await?;
-                    return Self::create_container(docker).await;
+                    return Self::create_container(docker, name, offline).await;
                 }
 
                 if !running {
                     info!("Sandbox container exists but not running, starting...");
-                    docker
-                        .start_container(CONTAINER_NAME, None)
-                        .await
-                        .map_err(|e| {
-                            ToolError::CommandFailed(format!("Container start failed: {}", e))
-                        })?;
+                    docker.start_container(name, None).await.map_err(|e| {
+                        ToolError::CommandFailed(format!("Container start failed: {}", e))
+                    })?;
                 }
 
                 Ok(())
             }
             Err(_) => {
-                info!("Creating persistent sandbox container: {}", CONTAINER_NAME);
+                info!("Creating persistent sandbox container: {}", name);
 
                 Self::ensure_image(docker).await?;
-                Self::create_container(docker).await
+                Self::create_container(docker, name, offline).await
             }
         }
     }
@@ -160,21 +171,20 @@ impl RunCommand {
 
         let _lock = CONTAINER_LOCK.lock().await;
 
-        docker
-            .stop_container(
-                CONTAINER_NAME,
-                Some(StopContainerOptionsBuilder::new().t(2).build()),
-            )
-            .await
-            .ok();
+        for name in [CONTAINER_NAME, OFFLINE_CONTAINER_NAME] {
+            docker
+                .stop_container(name, Some(StopContainerOptionsBuilder::new().t(2).build()))
+                .await
+                .ok();
 
-        docker
-            .remove_container(
-                CONTAINER_NAME,
-                Some(RemoveContainerOptionsBuilder::new().force(true).build()),
-            )
-            .await
-            .ok();
+            docker
+                .remove_container(
+                    name,
+                    Some(RemoveContainerOptionsBuilder::new().force(true).build()),
+                )
+                .await
+                .ok();
+        }
 
         let workspace = Self::workspace_path(config);
         if workspace.exists() {
@@ -185,13 +195,56 @@ impl RunCommand {
         Ok(())
     }
 
+    /// Stops the sandbox containers, killing any commands still running in
+    /// them. They are started again on the next command.
+    pub async fn stop_containers() -> Result<(), ToolError> {
+        let docker = Docker::connect_with_local_defaults()
+            .map_err(|e| ToolError::CommandFailed(format!("Docker connection failed: {}", e)))?;
+
+        for name in [CONTAINER_NAME, OFFLINE_CONTAINER_NAME] {
+            docker
+                .stop_container(name, Some(StopContainerOptionsBuilder::new().t(2).build()))
+                .await
+                .ok();
+        }
+        Ok(())
+    }
+
+    /// Reports the state of each sandbox container as `(name, status)`.
+    pub async fn container_status() -> Result<Vec<(&'static str, String)>, ToolError> {
+        let docker = Docker::connect_with_local_defaults()
+            .map_err(|e| ToolError::CommandFailed(format!("Docker connection failed: {}", e)))?;
+
+        let mut status = Vec::new();
+        for name in [CONTAINER_NAME, OFFLINE_CONTAINER_NAME] {
+            let state = match docker.inspect_container(name, None).await {
+                Ok(info) => {
+                    let state = info.state.as_ref();
+                    let running = state.and_then(|s| s.running).unwrap_or(false);
+                    let started = state
+                        .and_then(|s| s.started_at.as_deref())
+                        .unwrap_or("unknown");
+                    if running {
+                        format!("running since {}", started)
+                    } else {
+                        "stopped".to_string()
+                    }
+                }
+                Err(_) => "not created".to_string(),
+            };
+            status.push((name, state));
+        }
+        Ok(status)
+    }
+
     async fn exec_in_container(&self, command: &str) -> Result<String, ToolError> {
         debug!("Executing in persistent container: {}", command);
 
         let docker = Docker::connect_with_local_defaults()
             .map_err(|e| ToolError::CommandFailed(format!("Docker connection failed: {}", e)))?;
 
-        Self::ensure_container(&docker).await?;
+        let container = self.container_name();
+        Self::ensure_container(&docker, container, self.offline).await?;
 
         let exec_options = CreateExecOptions {
             cmd: Some(vec!["bash", "-c", command]),
@@ -202,13 +255,17 @@ impl RunCommand {
         };
 
         let exec = docker
-            .create_exec(CONTAINER_NAME, exec_options)
+            .create_exec(container, exec_options)
             .await
             .map_err(|e| ToolError::CommandFailed(format!("Exec creation failed: {}", e)))?;
 
         let exec_id = exec.id.clone();
 
-        match tokio::time::timeout(Duration::from_secs(self.config.commands.timeout), async {
+        let timeout = self.timeout.map_or(self.config.commands.timeout, |t| {
+            t.min(self.config.commands.timeout)
+        });
+
+        match tokio::time::timeout(Duration::from_secs(timeout), async {
             let start_result = docker
                 .start_exec(&exec.id, None)
                 .await
@@ -258,10 +315,7 @@ impl RunCommand {
             }
             Ok(Err(e)) => Err(e),
             Err(_) => {
-                warn!(
-                    "Command execution timed out after {}s",
-                    self.config.commands.timeout
-                );
+                warn!("Command execution timed out after {}s", timeout);
                 Err(ToolError::Timeout)
             }
         }
@@ -276,12 +330,17 @@ impl Tool for RunCommand {
     type Output = String;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        ToolDefinition {
-            name: Self::NAME.to_string(),
-            description: "Execute shell commands in a persistent Docker container with Python and Node.js pre-installed. \
+        let mut description = "Execute shell commands in a persistent Docker container with Python and Node.js pre-installed. \
                  Installed packages (apt-get install) and files persist across invocations. \
                  The /workspace directory is the working directory. Use python/python3, node, npm, and npx directly."
-                .to_string(),
+            .to_string();
+        if self.offline {
+            description.push_str(" Network access is disabled for this sandbox.");
+        }
+
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description,
             parameters: json!({
                 "type": "object",
                 "properties": {
@@ -1,5 +1,5 @@
 use super::error::ToolError;
-use crate::scheduler::Scheduler;
+use crate::{permissions::Role, scheduler::Scheduler};
 use rig::{completion::ToolDefinition, tool::Tool};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -15,8 +15,10 @@ pub struct ScheduleAddArgs {
 #[derive(Clone)]
 pub struct ScheduleAdd {
     pub scheduler: Arc<Scheduler>,
-    pub is_owner: bool,
+    pub role: Role,
     pub discord_channel_id: Option<u64>,
+    /// The requesting user's timezone, for the cron expression.
+    pub timezone: Option<String>,
 }
 
 impl Tool for ScheduleAdd {
@@ -35,7 +37,7 @@ impl Tool for ScheduleAdd {
                 "properties": {
                     "cron_expr": {
                         "type": "string",
-                        "description": "Cron expression (e.g., '0 0 9 * * *' for daily at 9am)"
+                        "description": "Cron expression (e.g., '0 0 9 * * *' for daily at 9am), in the user's timezone if they set one"
                     },
                     "prompt": {
                         "type": "string",
@@ -58,8 +60,9 @@ impl Tool for ScheduleAdd {
                 &args.cron_expr,
                 &args.prompt,
                 &args.description,
-                self.is_owner,
+                self.role,
                 self.discord_channel_id,
+                self.timezone.clone(),
             )
             .await
             .map_err(|e| ToolError::ScheduleFailed(e.to_string()))?;
@@ -105,8 +108,12 @@ impl Tool for ScheduleList {
         let mut output = format!("Scheduled tasks ({}):\n\n", tasks.len());
         for task in tasks {
             output.push_str(&format!(
-                "ID: {}\n  Cron: {}\n  Description: {}\n  Prompt: {}\n\n",
-                task.id, task.cron_expr, task.description, task.prompt
+                "ID: {}{}\n  Cron: {}\n  Description: {}\n  Prompt: {}\n\n",
+                task.id,
+                if task.paused { " (paused)" } else { "" },
+                task.cron_expr,
+                task.description,
+                task.prompt
             ));
         }
 
@@ -135,7 +142,7 @@ impl Tool for ScheduleDelete {
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: Self::NAME.to_string(),
-            description: "Remove a scheduled task by ID (owner only)".to_string(),
+            description: "Remove a scheduled task by ID (operators and admins only)".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
@@ -152,7 +159,8 @@ impl Tool for ScheduleDelete {
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         if !self.is_owner {
             return Err(ToolError::ScheduleFailed(
-                "Permission denied: only the bot owner can remove scheduled tasks".to_string(),
+                "Permission denied: only operators and admins can remove scheduled tasks"
+                    .to_string(),
             ));
         }
 
@@ -19,6 +19,11 @@ fn default_top_k() -> usize {
 #[derive(Clone)]
 pub struct SearchMemory {
     pub vectordb: Arc<VectorDb>,
+    pub max_top_k: usize,
+}
+
+impl SearchMemory {
+    pub const MAX_TOP_K: usize = 20;
 }
 
 impl Tool for SearchMemory {
@@ -43,7 +48,7 @@ impl Tool for SearchMemory {
                     },
                     "top_k": {
                         "type": "integer",
-                        "description": "Number of results to return (default: 5, max: 20)",
+                        "description": format!("Number of results to return (default: 5, max: {})", self.max_top_k),
                         "default": 5
                     }
                 },
@@ -53,7 +58,7 @@ impl Tool for SearchMemory {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let top_k = args.top_k.clamp(1, 20);
+        let top_k = args.top_k.clamp(1, self.max_top_k.max(1));
 
         let results = self
             .vectordb
@@ -24,9 +24,7 @@ pub struct TypstRender {
 
 impl TypstRender {
     async fn exec_in_container(config: Arc<Config>, command: &str) -> Result<String, ToolError> {
-        let runner = RunCommand {
-            config: config.clone(),
-        };
+        let runner = RunCommand::new(config);
         runner
             .call(super::run_command::RunCommandArgs {
                 command: command.to_string(),
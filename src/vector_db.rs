@@ -1,5 +1,6 @@
+use crate::agent::UserPreferences;
 use crate::embeddings::EmbeddingService;
-use crate::entity::{conversations, important};
+use crate::entity::{conversations, feedback, important, user_profiles};
 use anyhow::{Context, Result};
 use sea_orm::*;
 use std::path::{Path, PathBuf};
@@ -15,10 +16,16 @@ pub struct VectorDb {
     index: Arc<Mutex<Index>>,
     embeddings: Arc<dyn EmbeddingService>,
     index_path: PathBuf,
+    /// Leave turns rated 👎 out of semantic search.
+    exclude_downvoted: bool,
 }
 
 impl VectorDb {
-    pub async fn new(data_dir: &Path, embeddings: Arc<dyn EmbeddingService>) -> Result<Arc<Self>> {
+    pub async fn new(
+        data_dir: &Path,
+        embeddings: Arc<dyn EmbeddingService>,
+        exclude_downvoted: bool,
+    ) -> Result<Arc<Self>> {
         let db_path = data_dir.join("memory.db");
         std::fs::create_dir_all(data_dir)?;
         let db_url = format!("sqlite:{}", db_path.display());
@@ -33,6 +40,8 @@ impl VectorDb {
                 for mut table in [
                     schema.create_table_from_entity(conversations::Entity),
                     schema.create_table_from_entity(important::Entity),
+                    schema.create_table_from_entity(feedback::Entity),
+                    schema.create_table_from_entity(user_profiles::Entity),
                 ] {
                     let table = table.if_not_exists();
                     db.execute(table)?;
@@ -66,6 +75,7 @@ impl VectorDb {
             index: Arc::new(Mutex::new(index)),
             embeddings,
             index_path,
+            exclude_downvoted,
         });
 
         info!("VectorDB ready (usearch + rusqlite)");
@@ -77,7 +87,7 @@ impl VectorDb {
         author: &str,
         user_input: &str,
         assistant_response: &str,
-    ) -> Result<()> {
+    ) -> Result<String> {
         let id = uuid::Uuid::new_v4().to_string();
         let now = chrono::Utc::now().timestamp_micros();
 
@@ -89,7 +99,7 @@ impl VectorDb {
 
         let record = conversations::ActiveModel {
             rowid: NotSet,
-            id: Set(id),
+            id: Set(id.clone()),
             author: Set(author.to_string()),
             user_input: Set(user_input.to_string()),
             assistant_response: Set(assistant_response.to_string()),
@@ -120,7 +130,7 @@ impl VectorDb {
         })
         .await??;
 
-        Ok(())
+        Ok(id)
     }
 
     pub async fn recent_turns(&self, n: usize) -> Result<Vec<ConversationTurn>> {
@@ -152,6 +162,7 @@ impl VectorDb {
 
         let index = self.index.clone();
         let db_url = self.db_url.clone();
+        let exclude_downvoted = self.exclude_downvoted;
 
         tokio::task::spawn_blocking(move || -> Result<Vec<ConversationTurn>> {
             let db = Database::connect(&db_url)?;
@@ -172,10 +183,24 @@ impl VectorDb {
                 .filter(conversations::Column::Rowid.is_in(rowids))
                 .all(&db)?;
 
+            let downvoted: Vec<String> = if exclude_downvoted {
+                feedback::Entity::find()
+                    .filter(feedback::Column::TurnId.is_in(rows.iter().map(|r| r.id.clone())))
+                    .filter(feedback::Column::Rating.lt(0))
+                    .all(&db)?
+                    .into_iter()
+                    .map(|f| f.turn_id)
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
             let mut turns: Vec<ConversationTurn> = rows
                 .into_iter()
                 .map(|r| r.into())
-                .filter(|t: &ConversationTurn| !exclude.contains(&t.id))
+                .filter(|t: &ConversationTurn| {
+                    !exclude.contains(&t.id) && !downvoted.contains(&t.id)
+                })
                 .collect();
 
             turns.sort_by(|a, b| a.timestamp_micros.cmp(&b.timestamp_micros));
@@ -185,6 +210,68 @@ impl VectorDb {
         .await?
     }
 
+    /// Deletes a conversation turn and its embedding. Returns false when no
+    /// turn has the given id.
+    pub async fn delete_turn(&self, id: &str) -> Result<bool> {
+        let index = self.index.clone();
+        let index_path = self.index_path.clone();
+        let db_url = self.db_url.clone();
+        let id = id.to_string();
+
+        let deleted = tokio::task::spawn_blocking(move || -> Result<bool> {
+            let db = Database::connect(&db_url)?;
+            let Some(row) = conversations::Entity::find()
+                .filter(conversations::Column::Id.eq(&id))
+                .one(&db)?
+            else {
+                return Ok(false);
+            };
+            conversations::Entity::delete_by_id(row.rowid).exec(&db)?;
+            feedback::Entity::delete_many()
+                .filter(feedback::Column::TurnId.eq(&id))
+                .exec(&db)?;
+
+            let idx = index
+                .lock()
+                .map_err(|e| anyhow::anyhow!("index lock poisoned: {e}"))?;
+            idx.remove(row.rowid as u64)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            idx.save(index_path.to_str().context("non-UTF8 index path")?)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(true)
+        })
+        .await??;
+
+        if deleted {
+            info!("Deleted conversation turn");
+        }
+        Ok(deleted)
+    }
+
+    pub async fn stats(&self) -> Result<MemoryStats> {
+        let db_url = self.db_url.clone();
+        let indexed = self
+            .index
+            .lock()
+            .map_err(|e| anyhow::anyhow!("index lock poisoned: {e}"))?
+            .size();
+
+        let (turns, important) = tokio::task::spawn_blocking(move || -> Result<(u64, u64)> {
+            let db = Database::connect(&db_url)?;
+            Ok((
+                conversations::Entity::find().count(&db)?,
+                important::Entity::find().count(&db)?,
+            ))
+        })
+        .await??;
+
+        Ok(MemoryStats {
+            turns,
+            important,
+            indexed,
+        })
+    }
+
     pub async fn add_important(&self, content: &str) -> Result<String> {
         let id = uuid::Uuid::new_v4().to_string()[..8].to_string();
         let now = chrono::Utc::now().timestamp_micros();
@@ -239,6 +326,163 @@ impl VectorDb {
         Ok(affected > 0)
     }
 
+    /// Logs a finished answer so it can be rated later.
+    pub async fn record_answer(&self, entry: FeedbackEntry) -> Result<()> {
+        let record = feedback::ActiveModel {
+            rowid: NotSet,
+            turn_id: Set(entry.turn_id),
+            message_id: Set(entry.message_id as i64),
+            channel_id: Set(entry.channel_id as i64),
+            guild_id: Set(entry.guild_id.map(|g| g as i64)),
+            user_id: Set(entry.user_id as i64),
+            model: Set(entry.model),
+            total_tokens: Set(entry.total_tokens as i64),
+            rating: Set(None),
+            timestamp_us: Set(chrono::Utc::now().timestamp_micros()),
+        };
+
+        let db_url = self.db_url.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let db = Database::connect(&db_url)?;
+            feedback::Entity::insert(record).exec(&db)?;
+            Ok(())
+        })
+        .await?
+    }
+
+    pub async fn feedback(&self, turn_id: &str) -> Result<Option<FeedbackEntry>> {
+        let db_url = self.db_url.clone();
+        let turn_id = turn_id.to_string();
+
+        tokio::task::spawn_blocking(move || -> Result<Option<FeedbackEntry>> {
+            let db = Database::connect(&db_url)?;
+            let row = feedback::Entity::find()
+                .filter(feedback::Column::TurnId.eq(&turn_id))
+                .one(&db)?;
+            Ok(row.map(|r| r.into()))
+        })
+        .await?
+    }
+
+    /// Sets the rating of an answer. Returns false when it isn't logged.
+    pub async fn set_rating(&self, turn_id: &str, rating: i32) -> Result<bool> {
+        let db_url = self.db_url.clone();
+        let turn_id = turn_id.to_string();
+
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let db = Database::connect(&db_url)?;
+            let Some(row) = feedback::Entity::find()
+                .filter(feedback::Column::TurnId.eq(&turn_id))
+                .one(&db)?
+            else {
+                return Ok(false);
+            };
+            let mut row: feedback::ActiveModel = row.into();
+            row.rating = Set(Some(rating));
+            row.update(&db)?;
+            Ok(true)
+        })
+        .await?
+    }
+
+    /// Answers logged since `since_us`, oldest first.
+    pub async fn feedback_since(&self, since_us: i64) -> Result<Vec<FeedbackEntry>> {
+        let db_url = self.db_url.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<FeedbackEntry>> {
+            let db = Database::connect(&db_url)?;
+            let rows = feedback::Entity::find()
+                .filter(feedback::Column::TimestampUs.gte(since_us))
+                .order_by_asc(feedback::Column::TimestampUs)
+                .all(&db)?;
+            Ok(rows.into_iter().map(|r| r.into()).collect())
+        })
+        .await?
+    }
+
+    /// Answers rated 👎 since `since_us` with their conversation turns.
+    pub async fn downvoted_turns(
+        &self,
+        since_us: i64,
+    ) -> Result<Vec<(FeedbackEntry, ConversationTurn)>> {
+        let db_url = self.db_url.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<(FeedbackEntry, ConversationTurn)>> {
+            let db = Database::connect(&db_url)?;
+            let rated = feedback::Entity::find()
+                .filter(feedback::Column::TimestampUs.gte(since_us))
+                .filter(feedback::Column::Rating.lt(0))
+                .order_by_asc(feedback::Column::TimestampUs)
+                .all(&db)?;
+            let turns = conversations::Entity::find()
+                .filter(conversations::Column::Id.is_in(rated.iter().map(|f| f.turn_id.clone())))
+                .all(&db)?;
+
+            Ok(rated
+                .into_iter()
+                .filter_map(|f| {
+                    let turn = turns.iter().find(|t| t.id == f.turn_id)?.clone();
+                    Some((f.into(), turn.into()))
+                })
+                .collect())
+        })
+        .await?
+    }
+
+    /// The user's stored preferences, empty when none are set.
+    pub async fn user_preferences(&self, user_id: u64) -> Result<UserPreferences> {
+        let db_url = self.db_url.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<UserPreferences> {
+            let db = Database::connect(&db_url)?;
+            let row = user_profiles::Entity::find()
+                .filter(user_profiles::Column::UserId.eq(user_id as i64))
+                .one(&db)?;
+            Ok(row
+                .map(|r| UserPreferences {
+                    language: r.language,
+                    verbosity: r.verbosity,
+                    timezone: r.timezone,
+                    units: r.units,
+                    instructions: r.instructions,
+                })
+                .unwrap_or_default())
+        })
+        .await?
+    }
+
+    pub async fn save_user_preferences(
+        &self,
+        user_id: u64,
+        preferences: UserPreferences,
+    ) -> Result<()> {
+        let db_url = self.db_url.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let db = Database::connect(&db_url)?;
+            let existing = user_profiles::Entity::find()
+                .filter(user_profiles::Column::UserId.eq(user_id as i64))
+                .one(&db)?;
+            let mut row: user_profiles::ActiveModel = match existing {
+                Some(row) => row.into(),
+                None => user_profiles::ActiveModel {
+                    rowid: NotSet,
+                    user_id: Set(user_id as i64),
+                    ..Default::default()
+                },
+            };
+            row.language = Set(preferences.language);
+            row.verbosity = Set(preferences.verbosity);
+            row.timezone = Set(preferences.timezone);
+            row.units = Set(preferences.units);
+            row.instructions = Set(preferences.instructions);
+            row.updated_us = Set(chrono::Utc::now().timestamp_micros());
+            row.save(&db)?;
+            Ok(())
+        })
+        .await?
+    }
+
     pub async fn get_important_context(&self) -> Result<String> {
         let entries = self.list_important().await?;
         if entries.is_empty() {
@@ -283,6 +527,42 @@ impl From<conversations::Model> for ConversationTurn {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct FeedbackEntry {
+    pub turn_id: String,
+    pub message_id: u64,
+    pub channel_id: u64,
+    pub guild_id: Option<u64>,
+    pub user_id: u64,
+    pub model: String,
+    pub total_tokens: u64,
+    pub rating: Option<i32>,
+    pub timestamp_micros: i64,
+}
+
+impl From<feedback::Model> for FeedbackEntry {
+    fn from(r: feedback::Model) -> Self {
+        Self {
+            turn_id: r.turn_id,
+            message_id: r.message_id as u64,
+            channel_id: r.channel_id as u64,
+            guild_id: r.guild_id.map(|g| g as u64),
+            user_id: r.user_id as u64,
+            model: r.model,
+            total_tokens: r.total_tokens as u64,
+            rating: r.rating,
+            timestamp_micros: r.timestamp_us,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryStats {
+    pub turns: u64,
+    pub important: u64,
+    pub indexed: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct ImportantEntry {
     pub id: String,
@@ -0,0 +1,63 @@
+#!/usr/bin/env python3
+"""Minimal stdio MCP server used by the MCP client tests.
+
+Serves two tools over two `tools/list` pages so pagination is exercised:
+`echo` returns its `text` argument, `fail` returns a result flagged `isError`.
+"""
+
+import json
+import sys
+
+TOOLS = [
+    {
+        "name": "echo",
+        "description": "Echo the given text",
+        "inputSchema": {
+            "type": "object",
+            "properties": {"text": {"type": "string"}},
+            "required": ["text"],
+        },
+    },
+    {
+        "name": "fail",
+        "description": "Always fails",
+        "inputSchema": {"type": "object", "properties": {}},
+    },
+]
+
+
+def result(msg):
+    method = msg["method"]
+    args = msg.get("params") or {}
+    if method == "initialize":
+        return {
+            "protocolVersion": args.get("protocolVersion"),
+            "capabilities": {"tools": {}},
+            "serverInfo": {"name": "fixture", "version": "0.0.0"},
+        }
+    if method == "tools/list":
+        if args.get("cursor") == "page2":
+            return {"tools": TOOLS[1:]}
+        return {"tools": TOOLS[:1], "nextCursor": "page2"}
+    if method == "tools/call":
+        arguments = args.get("arguments") or {}
+        if args.get("name") == "echo":
+            text = arguments.get("text", "")
+            return {"content": [{"type": "text", "text": text}]}
+        if args.get("name") == "fail":
+            return {"content": [{"type": "text", "text": "boom"}], "isError": True}
+    return None
+
+
+for line in sys.stdin:
+    msg = json.loads(line)
+    if "id" not in msg:
+        continue
+    reply = {"jsonrpc": "2.0", "id": msg["id"]}
+    value = result(msg)
+    if value is None:
+        reply["error"] = {"code": -32601, "message": "Method not found"}
+    else:
+        reply["result"] = value
+    sys.stdout.write(json.dumps(reply) + "\n")
+    sys.stdout.flush()